@@ -1,3 +1,15 @@
+pub mod backend;
+pub mod compare;
+pub mod context;
+pub mod conversation;
+pub mod export;
+pub mod importer;
+pub mod index;
 pub mod init;
 pub mod llm;
 pub mod modules;
+pub mod project;
+pub mod sandbox;
+pub mod search;
+pub mod storage;
+pub mod tree;
@@ -0,0 +1,5 @@
+pub mod decoder;
+pub mod init;
+pub mod llamacpp;
+pub mod ollama;
+pub mod openai;
@@ -0,0 +1,188 @@
+// Streamed answers are received in HTTP chunks which don't follow the message boundaries: a
+// chunk can hold several messages or only a part of one. The decoders keep the incomplete end
+// of a chunk until the rest of it is received.
+
+// Split a byte stream in lines, used for newline delimited JSON
+#[derive(Default)]
+pub struct LineDecoder {
+    buffer: Vec<u8>,
+}
+
+impl LineDecoder {
+    // Add a chunk and return every line completed by it
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut lines = Vec::new();
+        while let Some(pos) = self.buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=pos).collect();
+            lines.push(Self::decode(&line[..line.len() - 1]));
+        }
+        lines
+    }
+
+    // Return the last line if the stream didn't end with a newline
+    pub fn finish(&mut self) -> Option<String> {
+        if self.buffer.is_empty() {
+            return None;
+        }
+
+        let line = Self::decode(&self.buffer);
+        self.buffer.clear();
+        Some(line)
+    }
+
+    // Lines are only decoded once complete, so a multi-byte character split between two chunks
+    // isn't broken
+    fn decode(line: &[u8]) -> String {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        String::from_utf8_lossy(line).into_owned()
+    }
+}
+
+// Decode server-sent events and return the data of each event, the other fields are ignored
+#[derive(Default)]
+pub struct SseDecoder {
+    lines: LineDecoder,
+    data: Vec<String>, // Data lines of the event being received
+}
+
+impl SseDecoder {
+    // Add a chunk and return the data of every event completed by it
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        let mut events = Vec::new();
+        for line in self.lines.push(chunk) {
+            if let Some(event) = self.read_line(&line) {
+                events.push(event);
+            }
+        }
+        events
+    }
+
+    // Return the last event if the stream didn't end with an empty line
+    pub fn finish(&mut self) -> Option<String> {
+        if let Some(line) = self.lines.finish() {
+            self.read_line(&line);
+        }
+        self.dispatch()
+    }
+
+    fn read_line(&mut self, line: &str) -> Option<String> {
+        // An empty line ends the event
+        if line.is_empty() {
+            return self.dispatch();
+        }
+
+        // Comments and other fields than data are skipped
+        if let Some(data) = line.strip_prefix("data:") {
+            self.data.push(data.strip_prefix(' ').unwrap_or(data).to_string());
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<String> {
+        if self.data.is_empty() {
+            return None;
+        }
+
+        let event = self.data.join("\n");
+        self.data.clear();
+        Some(event)
+    }
+}
+
+// How the messages of a streamed answer are delimited
+#[derive(Debug, Clone, Copy)]
+pub enum Framing {
+    Ndjson, // One JSON object per line
+    Sse,    // Server-sent events
+}
+
+// Decoder matching the framing of a backend
+pub enum StreamDecoder {
+    Ndjson(LineDecoder),
+    Sse(SseDecoder),
+}
+
+impl StreamDecoder {
+    pub fn new(framing: Framing) -> StreamDecoder {
+        match framing {
+            Framing::Ndjson => StreamDecoder::Ndjson(LineDecoder::default()),
+            Framing::Sse => StreamDecoder::Sse(SseDecoder::default()),
+        }
+    }
+
+    // Add a chunk and return every complete message, empty lines are skipped
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        let messages = match self {
+            StreamDecoder::Ndjson(decoder) => decoder.push(chunk),
+            StreamDecoder::Sse(decoder) => decoder.push(chunk),
+        };
+        messages.into_iter().filter(|m| !m.trim().is_empty()).collect()
+    }
+
+    // Return the last message if it wasn't terminated
+    pub fn finish(&mut self) -> Option<String> {
+        let message = match self {
+            StreamDecoder::Ndjson(decoder) => decoder.finish(),
+            StreamDecoder::Sse(decoder) => decoder.finish(),
+        };
+        message.filter(|m| !m.trim().is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_line_split_between_chunks_is_joined() {
+        let mut decoder = LineDecoder::default();
+        assert!(decoder.push(b"{\"a\":").is_empty());
+        assert_eq!(decoder.push(b"1}\n"), ["{\"a\":1}"]);
+        assert_eq!(decoder.finish(), None);
+    }
+
+    #[test]
+    fn a_chunk_can_hold_several_lines() {
+        let mut decoder = LineDecoder::default();
+        assert_eq!(decoder.push(b"first\nsecond\nthi"), ["first", "second"]);
+        assert_eq!(decoder.push(b"rd\n"), ["third"]);
+    }
+
+    #[test]
+    fn a_character_split_between_chunks_isnt_broken() {
+        let bytes = "café\n".as_bytes();
+        let split = bytes.len() - 2; // Inside the two bytes of `é`
+        let mut decoder = LineDecoder::default();
+        assert!(decoder.push(&bytes[..split]).is_empty());
+        assert_eq!(decoder.push(&bytes[split..]), ["café"]);
+    }
+
+    #[test]
+    fn carriage_returns_are_removed() {
+        let mut decoder = LineDecoder::default();
+        assert_eq!(decoder.push(b"one\r\ntwo\r"), ["one"]);
+        assert_eq!(decoder.push(b"\n"), ["two"]);
+    }
+
+    #[test]
+    fn the_data_lines_of_an_event_are_joined() {
+        let mut decoder = SseDecoder::default();
+        assert!(decoder.push(b": comment\nevent: message\ndata: first\n").is_empty());
+        assert_eq!(decoder.push(b"data:second\n\ndata: next\r\n\r\n"), ["first\nsecond", "next"]);
+    }
+
+    #[test]
+    fn finish_returns_the_last_message() {
+        let mut lines = StreamDecoder::new(Framing::Ndjson);
+        assert_eq!(lines.push(b"{}\n\n{\"done\":"), ["{}"]);
+        assert_eq!(lines.push(b"true}"), Vec::<String>::new());
+        assert_eq!(lines.finish().as_deref(), Some("{\"done\":true}"));
+        assert_eq!(lines.finish(), None);
+
+        let mut events = StreamDecoder::new(Framing::Sse);
+        assert!(events.push(b"data: [DONE]").is_empty());
+        assert_eq!(events.finish().as_deref(), Some("[DONE]"));
+    }
+}
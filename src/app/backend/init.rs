@@ -0,0 +1,88 @@
+use crate::app::backend::decoder::Framing;
+use crate::app::backend::{llamacpp::LlamaCpp, ollama::Ollama, openai::OpenAi};
+use crate::app::llm::{Message, Metrics, Options};
+use crate::error::{Error, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::fmt::Debug;
+
+// Read the error object of a response, Ollama sends `{"error": "..."}` while OpenAI compatible
+// servers send `{"error": {"message": "..."}}`
+pub fn server_error(response: &Value) -> Option<Error> {
+    let error = response.get("error")?;
+    let message = match error {
+        Value::String(message) => message.clone(),
+        error => error["message"]
+            .as_str()
+            .map(|m| m.to_string())
+            .unwrap_or_else(|| error.to_string()),
+    };
+    Some(Error::Backend(message))
+}
+
+// What a line of a streamed answer contains
+pub enum StreamEvent {
+    Token(String),         // Part of the answer
+    Metrics(Metrics),      // Usage of the request, sent before the end
+    Done(Option<Metrics>), // End of the answer, with the usage if sent with it
+    Skip,                  // Nothing useful (keep-alive, role announcement...)
+}
+
+// Wire format of an LLM server API. The HTTP requests are made by `LLM`, a backend only knows
+// how to build the request body and how to read the responses
+pub trait Backend: Debug + Send + Sync {
+    // Body of a chat request, `tools` is only given when tool calls are expected
+    fn chat_body(
+        &self,
+        model: &str,
+        messages: &[Message],
+        options: &Options,
+        stream: bool,
+        tools: Option<&Value>,
+    ) -> Value;
+
+    // How the messages of a streamed response are delimited
+    fn framing(&self) -> Framing;
+
+    // Read one message of a streamed response, as split by the decoder of `framing`
+    fn parse_stream_message(&self, message: &str) -> Result<StreamEvent>;
+
+    // Extract the tool calls of a non streamed response, as a list of
+    // `{"function": {"name": ..., "arguments": {...}}}`
+    fn tool_calls(&self, response: &Value) -> Option<Value>;
+}
+
+// Server API used by an LLM, selected by the `provider` field of its configuration
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Provider {
+    #[default]
+    Ollama,
+    OpenAi,
+    LlamaCpp,
+}
+
+impl Provider {
+    pub fn backend(&self) -> &'static dyn Backend {
+        match self {
+            Provider::Ollama => &Ollama,
+            Provider::OpenAi => &OpenAi,
+            Provider::LlamaCpp => &LlamaCpp,
+        }
+    }
+}
+
+// Messages as expected by chat APIs, only the role and the content are sent
+pub fn wire_messages(messages: &[Message]) -> Value {
+    Value::Array(
+        messages
+            .iter()
+            .map(|m| serde_json::json!({"role": m.role.to_string(), "content": m.content}))
+            .collect(),
+    )
+}
+
+// Convert a duration sent in nanoseconds to milliseconds
+pub fn ns_to_ms(value: &Value) -> Option<u64> {
+    value.as_u64().map(|ns| ns / 1_000_000)
+}
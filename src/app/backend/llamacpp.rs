@@ -0,0 +1,62 @@
+use crate::app::backend::decoder::Framing;
+use crate::app::backend::init::{Backend, StreamEvent};
+use crate::app::backend::openai::OpenAi;
+use crate::app::llm::{Message, Metrics, Options};
+use crate::error::Result;
+use serde_json::Value;
+
+// llama.cpp `llama-server` chat API. It follows the OpenAI format, with some extensions
+#[derive(Debug)]
+pub struct LlamaCpp;
+
+impl Backend for LlamaCpp {
+    fn chat_body(
+        &self,
+        model: &str,
+        messages: &[Message],
+        options: &Options,
+        stream: bool,
+        tools: Option<&Value>,
+    ) -> Value {
+        let mut body = OpenAi.chat_body(model, messages, options, stream, tools);
+
+        // Reuse the KV cache of the previous request, the history is mostly the same between
+        // two messages
+        body["cache_prompt"] = Value::from(true);
+        body
+    }
+
+    fn framing(&self) -> Framing {
+        OpenAi.framing()
+    }
+
+    // The last event has the timings of the request, more detailed than the OpenAI usage
+    fn parse_stream_message(&self, message: &str) -> Result<StreamEvent> {
+        let event = OpenAi.parse_stream_message(message)?;
+        if matches!(event, StreamEvent::Token(_) | StreamEvent::Done(_)) {
+            return Ok(event);
+        }
+
+        let answer: Value = serde_json::from_str(message)?;
+        let timings = &answer["timings"];
+        if !timings.is_object() {
+            return Ok(event);
+        }
+
+        let ms = |value: &Value| value.as_f64().map(|ms| ms as u64);
+        let prompt_duration_ms = ms(&timings["prompt_ms"]);
+        let eval_duration_ms = ms(&timings["predicted_ms"]);
+        Ok(StreamEvent::Metrics(Metrics {
+            prompt_tokens: timings["prompt_n"].as_u64(),
+            completion_tokens: timings["predicted_n"].as_u64(),
+            total_duration_ms: prompt_duration_ms.zip(eval_duration_ms).map(|(p, e)| p + e),
+            prompt_duration_ms,
+            eval_duration_ms,
+            ..Default::default()
+        }))
+    }
+
+    fn tool_calls(&self, response: &Value) -> Option<Value> {
+        OpenAi.tool_calls(response)
+    }
+}
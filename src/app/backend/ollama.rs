@@ -0,0 +1,71 @@
+use crate::app::backend::decoder::Framing;
+use crate::app::backend::init::{ns_to_ms, server_error, wire_messages, Backend, StreamEvent};
+use crate::app::llm::{Message, Metrics, Options};
+use crate::error::Result;
+use serde_json::Value;
+
+// Ollama `/api/chat` API, streamed as one JSON object per line
+#[derive(Debug)]
+pub struct Ollama;
+
+impl Backend for Ollama {
+    fn chat_body(
+        &self,
+        model: &str,
+        messages: &[Message],
+        options: &Options,
+        stream: bool,
+        tools: Option<&Value>,
+    ) -> Value {
+        let mut body = serde_json::json!({
+            "model": model,
+            "messages": wire_messages(messages),
+            "options": options,
+            "stream": stream});
+
+        if let Some(keep_alive) = &options.keep_alive {
+            body["keep_alive"] = keep_alive.clone();
+        }
+
+        if let Some(tools) = tools {
+            body["tools"] = tools.clone();
+        }
+        body
+    }
+
+    fn framing(&self) -> Framing {
+        Framing::Ndjson
+    }
+
+    fn parse_stream_message(&self, message: &str) -> Result<StreamEvent> {
+        let answer: Value = serde_json::from_str(message)?;
+
+        if let Some(e) = server_error(&answer) {
+            return Err(e);
+        }
+
+        // The last message has the counters and durations of the request
+        if answer["done"].as_bool().unwrap_or(false) {
+            return Ok(StreamEvent::Done(Some(Metrics {
+                prompt_tokens: answer["prompt_eval_count"].as_u64(),
+                completion_tokens: answer["eval_count"].as_u64(),
+                total_duration_ms: ns_to_ms(&answer["total_duration"]),
+                load_duration_ms: ns_to_ms(&answer["load_duration"]),
+                prompt_duration_ms: ns_to_ms(&answer["prompt_eval_duration"]),
+                eval_duration_ms: ns_to_ms(&answer["eval_duration"]),
+            })));
+        }
+
+        let msg = answer["message"]["content"].as_str().unwrap_or("\n");
+        Ok(StreamEvent::Token(msg.to_string()))
+    }
+
+    // Use tools functionnality of Ollama, only some models supports it:
+    // https://ollama.com/search?c=tools
+    fn tool_calls(&self, response: &Value) -> Option<Value> {
+        response
+            .get("message")
+            .and_then(|msg| msg.get("tool_calls"))
+            .cloned()
+    }
+}
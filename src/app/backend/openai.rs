@@ -0,0 +1,100 @@
+use crate::app::backend::decoder::Framing;
+use crate::app::backend::init::{server_error, wire_messages, Backend, StreamEvent};
+use crate::app::llm::{Message, Metrics, Options};
+use crate::error::Result;
+use serde_json::Value;
+
+// OpenAI compatible `/v1/chat/completions` API, streamed with server-sent events
+#[derive(Debug)]
+pub struct OpenAi;
+
+impl Backend for OpenAi {
+    // The context size and keep alive are settings of the server, they can't be sent
+    fn chat_body(
+        &self,
+        model: &str,
+        messages: &[Message],
+        options: &Options,
+        stream: bool,
+        tools: Option<&Value>,
+    ) -> Value {
+        let mut body = serde_json::json!({
+            "model": model,
+            "messages": wire_messages(messages),
+            "stream": stream});
+
+        // Ask for the usage in the last event of the stream
+        if stream {
+            body["stream_options"] = serde_json::json!({"include_usage": true});
+        }
+
+        if let Some(temperature) = options.temperature {
+            body["temperature"] = Value::from(temperature);
+        }
+        if let Some(top_p) = options.top_p {
+            body["top_p"] = Value::from(top_p);
+        }
+        if let Some(seed) = options.seed {
+            body["seed"] = Value::from(seed);
+        }
+        if let Some(stop) = &options.stop {
+            body["stop"] = Value::from(stop.clone());
+        }
+        if let Some(num_predict) = options.num_predict {
+            body["max_tokens"] = Value::from(num_predict);
+        }
+
+        if let Some(tools) = tools {
+            body["tools"] = tools.clone();
+            body["tool_choice"] = Value::from("required");
+        }
+        body
+    }
+
+    fn framing(&self) -> Framing {
+        Framing::Sse
+    }
+
+    fn parse_stream_message(&self, message: &str) -> Result<StreamEvent> {
+        if message.trim() == "[DONE]" {
+            return Ok(StreamEvent::Done(None));
+        }
+
+        let answer: Value = serde_json::from_str(message)?;
+
+        if let Some(e) = server_error(&answer) {
+            return Err(e);
+        }
+        match answer["choices"][0]["delta"]["content"].as_str() {
+            Some(msg) => Ok(StreamEvent::Token(msg.to_string())),
+            None if answer["usage"].is_object() => Ok(StreamEvent::Metrics(Metrics {
+                prompt_tokens: answer["usage"]["prompt_tokens"].as_u64(),
+                completion_tokens: answer["usage"]["completion_tokens"].as_u64(),
+                ..Default::default()
+            })),
+            None => Ok(StreamEvent::Skip),
+        }
+    }
+
+    // Tool calls arguments are sent as a JSON string, they are parsed to match the other
+    // backends
+    fn tool_calls(&self, response: &Value) -> Option<Value> {
+        let calls = response["choices"][0]["message"]["tool_calls"].as_array()?;
+
+        Some(Value::Array(
+            calls
+                .iter()
+                .map(|call| {
+                    let arguments = match &call["function"]["arguments"] {
+                        Value::String(args) => serde_json::from_str(args).unwrap_or(Value::Null),
+                        args => args.clone(),
+                    };
+                    serde_json::json!({
+                        "function": {
+                            "name": call["function"]["name"],
+                            "arguments": arguments}})
+                })
+                .collect(),
+        ))
+    }
+}
@@ -0,0 +1,90 @@
+use crate::app::llm::{Answer, Message, MessageType, TokenSender, LLM};
+use crate::app::modules::chat;
+use crate::error::Result;
+use crate::helper::config::load_config;
+use serde::Deserialize;
+use uuid::Uuid;
+
+// Models the answers are compared with, the chat configuration is used when it doesn't exist
+pub const COMPARE_CONFIG: &str = "config/compare.json";
+
+// Content of `config/compare.json`
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct CompareConfig {
+    pub config: String,      // LLM configuration the alternatives are generated with
+    pub models: Vec<String>, // One alternative per model, its model when empty
+}
+
+impl Default for CompareConfig {
+    fn default() -> Self {
+        Self { config: chat::CHAT_CONFIG.to_string(), models: Vec::new() }
+    }
+}
+
+impl CompareConfig {
+    pub fn load() -> Result<CompareConfig> {
+        load_config(COMPARE_CONFIG)
+    }
+
+    // Models given by the user, or the configured ones
+    pub fn models(&self, models: &[String]) -> Result<Vec<String>> {
+        if !models.is_empty() {
+            return Ok(models.to_vec());
+        }
+        if !self.models.is_empty() {
+            return Ok(self.models.clone());
+        }
+        Ok(vec![LLM::new(&self.config)?.model])
+    }
+}
+
+// Another answer to the same message
+pub struct Alternative {
+    pub label: String, // Model and seed which generated it
+    pub message: Message,
+    pub done: bool,
+}
+
+// Answers of several models to the same message, shown side by side until one is picked
+pub struct Comparison {
+    pub index: usize, // Answer compared, in `App::messages`
+    pub alternatives: Vec<Alternative>, // The answer compared first
+}
+
+impl Comparison {
+    pub fn new(index: usize, answer: &Message, models: &[String]) -> Comparison {
+        let current = Alternative {
+            label: format!("current ({})", answer.meta.model.as_deref().unwrap_or("unknown model")),
+            message: answer.clone(),
+            done: true,
+        };
+        let others = models.iter().map(|model| Alternative {
+            label: model.clone(),
+            message: Message::new(MessageType::ASSISTANT, String::new()),
+            done: false,
+        });
+
+        Comparison { index, alternatives: std::iter::once(current).chain(others).collect() }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.alternatives.iter().any(|alternative| !alternative.done)
+    }
+}
+
+// A new seed for each alternative, so the same model gives another answer
+fn random_seed() -> i64 {
+    (Uuid::new_v4().as_u128() >> 80) as i64
+}
+
+// Answer the history again with `model`, using the LLM configuration of the comparison
+pub async fn generate(config: &str, model: &str, messages: Vec<Message>, tokens: TokenSender) -> Result<(Answer, i64)> {
+    let mut llm = LLM::new(config)?;
+    let seed = random_seed();
+    llm.model = model.to_string();
+    llm.options.seed = Some(seed);
+
+    let answer = chat::ask_with(llm, messages, &tokens).await?;
+    Ok((answer, seed))
+}
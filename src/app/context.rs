@@ -0,0 +1,225 @@
+use crate::app::llm::{Message, MessageType};
+use serde::Deserialize;
+
+// Context window size used when neither `max_tokens` nor the `num_ctx` option is set, it's the
+// default of Ollama
+const DEFAULT_CONTEXT_SIZE: u64 = 2048;
+
+// Tokens added by the chat template around each message
+const MESSAGE_OVERHEAD: u64 = 4;
+
+// How the history is reduced when it doesn't fit in the context window of the model
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Strategy {
+    #[default]
+    SlidingWindow, // Drop the oldest messages until the history fits
+    KeepLast,      // Only keep the system messages and the `keep_last` last messages
+    Summarize,     // Replace the oldest messages by a summary made by the `summarizer` LLM
+    Rolling,       // Like `summarize`, but the summary is updated with the newly dropped messages
+}
+
+// `context` section of a LLM configuration
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ContextConfig {
+    pub strategy: Strategy,
+    pub keep_last: usize,         // Messages always kept by `keep_last`, `summarize` and `rolling`
+    pub chars_per_token: f64,     // Used to estimate the number of tokens of a text
+    pub max_tokens: Option<u64>,  // Context window size, `num_ctx` option by default
+    pub reserve: u64,             // Tokens left for the answer
+    pub summarizer: String,       // Configuration of the LLM used by `summarize` and `rolling`
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        Self {
+            strategy: Strategy::default(),
+            keep_last: 6,
+            chars_per_token: 4.0,
+            max_tokens: None,
+            reserve: 512,
+            summarizer: "config/resume-LLM.json".to_string(),
+        }
+    }
+}
+
+// Tokens used by the prompt compared to the tokens available
+#[derive(Debug, Clone, Copy)]
+pub struct ContextUsage {
+    pub used: u64,
+    pub budget: u64,
+}
+
+impl ContextConfig {
+    // Tokens available for the prompt, `num_ctx` is the context size option of the model
+    pub fn budget(&self, num_ctx: Option<u64>) -> u64 {
+        self.max_tokens
+            .or(num_ctx)
+            .unwrap_or(DEFAULT_CONTEXT_SIZE)
+            .saturating_sub(self.reserve)
+    }
+
+    pub fn estimate_text(&self, text: &str) -> u64 {
+        (text.chars().count() as f64 / self.chars_per_token.max(1.0)).ceil() as u64
+    }
+
+    pub fn estimate(&self, messages: &[Message]) -> u64 {
+        messages
+            .iter()
+            .map(|m| self.estimate_text(&m.content) + MESSAGE_OVERHEAD)
+            .sum()
+    }
+
+    // Split the history between the messages kept in the prompt and the older ones dropped.
+    // System messages and the last turn are always kept: the last message with the user messages
+    // right before it, as the modules add their instructions after the question of the user. If
+    // they don't fit, the end of the question is cut before the instructions
+    pub fn fit(&self, messages: &[Message], budget: u64) -> (Vec<Message>, Vec<Message>) {
+        let is_system = |m: &Message| matches!(m.role, MessageType::SYSTEM);
+        let conv: Vec<&Message> = messages.iter().filter(|m| !is_system(m)).collect();
+        let nb_conv = conv.len();
+        let nb_last = match conv.last() {
+            Some(last) if matches!(last.role, MessageType::USER) => {
+                conv.iter().rev().take_while(|m| matches!(m.role, MessageType::USER)).count()
+            }
+            Some(_) => 1,
+            None => 0,
+        };
+        let max_start = nb_conv - nb_last;
+
+        let over_budget = self.estimate(messages) > budget;
+        let mut start = match self.strategy {
+            Strategy::KeepLast => nb_conv.saturating_sub(self.keep_last),
+            Strategy::Summarize | Strategy::Rolling if over_budget => nb_conv.saturating_sub(self.keep_last),
+            _ => 0,
+        }
+        .min(max_start);
+
+        let split = |start: usize| {
+            let mut kept = Vec::new();
+            let mut dropped = Vec::new();
+            let mut conv_index = 0;
+            for m in messages {
+                if is_system(m) {
+                    kept.push(m.clone());
+                    continue;
+                }
+                if conv_index < start {
+                    dropped.push(m.clone());
+                } else {
+                    kept.push(m.clone());
+                }
+                conv_index += 1;
+            }
+            (kept, dropped)
+        };
+
+        let (mut kept, mut dropped) = split(start);
+        while self.estimate(&kept) > budget && start < max_start {
+            start += 1;
+            (kept, dropped) = split(start);
+        }
+
+        let used = self.estimate(&kept);
+        if used > budget {
+            let mut excess = ((used - budget) as f64 * self.chars_per_token.max(1.0)).ceil() as usize;
+            for message in kept.iter_mut().filter(|m| !is_system(m)).skip(nb_conv - start - nb_last) {
+                let length = message.content.chars().count();
+                let cut = excess.min(length);
+                message.content = message.content.chars().take(length - cut).collect();
+                excess -= cut;
+            }
+        }
+
+        (kept, dropped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // One token per char, each message costs its length and the template overhead
+    fn config(strategy: Strategy, keep_last: usize) -> ContextConfig {
+        ContextConfig { strategy, keep_last, chars_per_token: 1.0, ..ContextConfig::default() }
+    }
+
+    fn history(roles: &[MessageType]) -> Vec<Message> {
+        roles
+            .iter()
+            .enumerate()
+            .map(|(i, role)| Message::new(role.clone(), format!("message {:>2}", i)))
+            .collect()
+    }
+
+    fn contents(messages: &[Message]) -> Vec<&str> {
+        messages.iter().map(|m| m.content.as_str()).collect()
+    }
+
+    #[test]
+    fn sliding_window_drops_the_oldest_messages() {
+        use MessageType::*;
+        let messages = history(&[SYSTEM, USER, ASSISTANT, USER, ASSISTANT, USER]);
+        let (kept, dropped) = config(Strategy::SlidingWindow, 6).fit(&messages, 3 * 14);
+
+        assert_eq!(contents(&kept), ["message  0", "message  4", "message  5"]);
+        assert_eq!(contents(&dropped), ["message  1", "message  2", "message  3"]);
+
+        let (kept, dropped) = config(Strategy::SlidingWindow, 6).fit(&messages, 1000);
+        assert_eq!(kept.len(), 6);
+        assert!(dropped.is_empty());
+    }
+
+    #[test]
+    fn keep_last_drops_older_messages_even_if_they_fit() {
+        use MessageType::*;
+        let messages = history(&[SYSTEM, USER, ASSISTANT, USER, ASSISTANT, USER]);
+        let (kept, dropped) = config(Strategy::KeepLast, 2).fit(&messages, 1000);
+
+        assert_eq!(contents(&kept), ["message  0", "message  4", "message  5"]);
+        assert_eq!(dropped.len(), 3);
+    }
+
+    #[test]
+    fn the_question_is_kept_with_the_instructions_after_it() {
+        use MessageType::*;
+        let mut messages = history(&[USER, ASSISTANT, USER, USER]);
+        messages[2].content = "q".repeat(20);
+        messages[3].content = "persona".to_string();
+
+        // Nothing but the question and the instructions fits, the end of the question is cut
+        let (kept, dropped) = config(Strategy::KeepLast, 1).fit(&messages, 30);
+        assert_eq!(contents(&kept), ["q".repeat(15).as_str(), "persona"]);
+        assert_eq!(dropped.len(), 2);
+    }
+
+    #[test]
+    fn the_last_message_is_cut_when_it_is_alone() {
+        use MessageType::*;
+        let mut messages = history(&[SYSTEM, USER]);
+        messages[1].content = "a".repeat(30);
+
+        let (kept, dropped) = config(Strategy::SlidingWindow, 6).fit(&messages, 40);
+        assert_eq!(contents(&kept), ["message  0", "a".repeat(22).as_str()]);
+        assert!(dropped.is_empty());
+    }
+
+    #[test]
+    fn the_history_is_fitted_again_with_its_summary() {
+        use MessageType::*;
+        let messages = history(&[USER, ASSISTANT, USER, ASSISTANT, USER, ASSISTANT]);
+        let config = config(Strategy::Summarize, 3);
+
+        // Over budget, only the `keep_last` messages are kept, the others are summarized
+        let (mut kept, dropped) = config.fit(&messages, 50);
+        assert_eq!(contents(&kept), ["message  3", "message  4", "message  5"]);
+        assert_eq!(dropped.len(), 3);
+
+        // The summary is a system message, older messages are dropped to make room for it
+        kept.insert(0, Message::new(SYSTEM, "s".repeat(16)));
+        let (kept, _) = config.fit(&kept, 50);
+        assert_eq!(contents(&kept), ["s".repeat(16).as_str(), "message  4", "message  5"]);
+        assert!(config.estimate(&kept) <= 50);
+    }
+}
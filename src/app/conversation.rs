@@ -0,0 +1,110 @@
+use crate::app::llm::{Message, MessageType};
+use crate::app::storage::init as storage;
+use crate::error::{Error, Result};
+use chrono::{DateTime, Local};
+use uuid::Uuid;
+
+// Find the conversation matching an id or the start of an id, like the one shown in the header
+pub fn resolve(prefix: &str) -> Result<Uuid> {
+    let prefix = prefix.trim();
+    if prefix.is_empty() {
+        return Err(Error::Storage("no conversation id given".to_string()));
+    }
+
+    let mut matches: Vec<Uuid> = storage::open()?
+        .ids()?
+        .into_iter()
+        .filter(|id| id.to_string().starts_with(prefix))
+        .collect();
+
+    match matches.len() {
+        0 => Err(Error::Storage(format!("no conversation matching {}", prefix))),
+        1 => Ok(matches.remove(0)),
+        n => Err(Error::Storage(format!("{} conversations match {}, give more of the id", n, prefix))),
+    }
+}
+
+// Read back the messages saved by `Message::save_message`
+pub fn load(conv_id: &Uuid) -> Result<Vec<Message>> {
+    storage::open()?.load(conv_id)
+}
+
+// Length of the first user message used as title when the conversation has no title
+const TITLE_LENGTH: usize = 40;
+
+// What the conversation browser shows about a saved conversation
+pub struct ConvSummary {
+    pub id: Uuid,
+    pub date: DateTime<Local>, // Last time a message was saved
+    pub title: String,         // Given title or start of the first user message
+    pub tags: Vec<String>,
+    pub nb_messages: usize,
+}
+
+// Every saved conversation, the most recent first
+pub fn list() -> Result<Vec<ConvSummary>> {
+    let storage = storage::open()?;
+
+    let mut summaries = Vec::new();
+    for id in storage.ids()? {
+        let date = storage.updated(&id).unwrap_or_else(|_| Local::now());
+        let messages = storage.load(&id)?;
+        let title = display_title(&id, &messages);
+        let tags = storage.tags(&id)?;
+
+        summaries.push(ConvSummary { id, date, title, tags, nb_messages: messages.len() });
+    }
+
+    summaries.sort_by_key(|conv| std::cmp::Reverse(conv.date));
+    Ok(summaries)
+}
+
+fn default_title(messages: &[Message]) -> String {
+    messages
+        .iter()
+        .find(|m| matches!(m.role, MessageType::USER))
+        .map(|m| {
+            let first_line = m.content.lines().next().unwrap_or("");
+            let mut title: String = first_line.chars().take(TITLE_LENGTH).collect();
+            if first_line.chars().count() > TITLE_LENGTH {
+                title.push_str("...");
+            }
+            title
+        })
+        .unwrap_or_else(|| "(empty)".to_string())
+}
+
+// Title given to a conversation, if any
+pub fn title(conv_id: &Uuid) -> Option<String> {
+    storage::open().and_then(|storage| storage.title(conv_id)).ok().flatten()
+}
+
+// Given title or start of the first user message
+pub fn display_title(conv_id: &Uuid, messages: &[Message]) -> String {
+    title(conv_id).unwrap_or_else(|| default_title(messages))
+}
+
+pub fn tags(conv_id: &Uuid) -> Result<Vec<String>> {
+    storage::open()?.tags(conv_id)
+}
+
+pub fn rename(conv_id: &Uuid, title: &str) -> Result<()> {
+    storage::open()?.rename(conv_id, title)
+}
+
+// Replace the tags of a conversation, duplicates and empty tags are removed
+pub fn set_tags(conv_id: &Uuid, tags: &[String]) -> Result<()> {
+    let mut tags: Vec<String> = tags
+        .iter()
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect();
+    tags.sort();
+    tags.dedup();
+    storage::open()?.set_tags(conv_id, &tags)
+}
+
+// Remove a conversation with its title and tags
+pub fn delete(conv_id: &Uuid) -> Result<()> {
+    storage::open()?.delete(conv_id)
+}
@@ -0,0 +1,319 @@
+use crate::app::conversation;
+use crate::app::llm::MessageType;
+use crate::app::modules::resume::is_resume;
+use crate::app::tree::ConvTree;
+use crate::error::{Error, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use uuid::Uuid;
+
+// Directory where the UI writes the exported conversations
+pub const EXPORT_DIR: &str = "export";
+
+// Value of the `format` field of the JSON export, see doc/export.md
+pub const JSON_FORMAT: &str = "nai-conversation";
+pub const JSON_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Markdown,
+    Html,
+    Json,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Result<Format> {
+        match name {
+            "md" | "markdown" => Ok(Format::Markdown),
+            "html" => Ok(Format::Html),
+            "json" => Ok(Format::Json),
+            _ => Err(Error::Config(format!("unknown export format {}, use md, html or json", name))),
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Markdown => "md",
+            Format::Html => "html",
+            Format::Json => "json",
+        }
+    }
+}
+
+// What an export contains and where it's written, parsed from `<format> [--resume] [--sources]
+// [-o <path>]`
+#[derive(Debug)]
+pub struct ExportRequest {
+    pub format: Format,
+    pub resume: bool,           // Add the last resume of the conversation
+    pub sources: bool,          // Add the documents used by each answer
+    pub output: Option<String>, // File written, the caller chooses when not given
+}
+
+impl ExportRequest {
+    pub fn parse(args: &[&str]) -> Result<ExportRequest> {
+        let mut request = ExportRequest {
+            format: Format::Markdown,
+            resume: false,
+            sources: false,
+            output: None,
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match *arg {
+                "--resume" => request.resume = true,
+                "--sources" => request.sources = true,
+                "-o" | "--output" => {
+                    let path = args.next().ok_or(Error::Config("-o needs a file path".to_string()))?;
+                    request.output = Some(path.to_string());
+                }
+                format => request.format = Format::parse(format)?,
+            }
+        }
+        Ok(request)
+    }
+}
+
+// A message as written in the JSON export
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ExportedMessage {
+    pub role: String, // user, assistant or system
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<DateTime<Local>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub route: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<String>,
+}
+
+// Root object of the JSON export, documented in doc/export.md
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ExportedConv {
+    pub format: String,
+    pub version: u32,
+    pub id: Uuid,
+    pub title: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub exported: DateTime<Local>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resume: Option<String>,
+    pub messages: Vec<ExportedMessage>,
+}
+
+impl ExportedConv {
+    // Read a saved conversation, keeping only what the request asks for
+    pub fn load(conv_id: &Uuid, request: &ExportRequest) -> Result<ExportedConv> {
+        // Only the most recent branch is exported
+        let messages = ConvTree::new(conversation::load(conv_id)?).current();
+
+        let resume = if request.resume {
+            messages.iter().rev().find(|m| is_resume(m)).map(|m| m.content.clone())
+        } else {
+            None
+        };
+
+        let exported = messages
+            .iter()
+            .filter(|m| !is_resume(m) && !matches!(m.role, MessageType::SYSTEM))
+            .map(|m| ExportedMessage {
+                role: m.role.to_string(),
+                content: m.content.clone(),
+                timestamp: m.meta.timestamp,
+                route: m.meta.route.clone(),
+                model: m.meta.model.clone(),
+                sources: if request.sources { m.meta.sources.clone() } else { Vec::new() },
+            })
+            .collect();
+
+        Ok(ExportedConv {
+            format: JSON_FORMAT.to_string(),
+            version: JSON_VERSION,
+            id: *conv_id,
+            title: conversation::display_title(conv_id, &messages),
+            tags: conversation::tags(conv_id)?,
+            exported: Local::now(),
+            resume,
+            messages: exported,
+        })
+    }
+
+    pub fn render(&self, format: Format) -> Result<String> {
+        match format {
+            Format::Markdown => Ok(self.to_markdown()),
+            Format::Html => Ok(self.to_html()),
+            Format::Json => Ok(serde_json::to_string_pretty(self)?),
+        }
+    }
+
+    fn speaker(message: &ExportedMessage) -> &'static str {
+        match message.role.as_str() {
+            "user" => "You",
+            "system" => "System",
+            _ => "Néo AI",
+        }
+    }
+
+    // Date, module and model of a message, when known
+    fn details(message: &ExportedMessage) -> String {
+        let details: Vec<String> = [
+            message.timestamp.map(|date| date.format("%Y-%m-%d %H:%M").to_string()),
+            message.route.clone(),
+            message.model.clone(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        details.join(", ")
+    }
+
+    fn header(&self) -> String {
+        format!("Conversation {} exported on {}", self.id, self.exported.format("%Y-%m-%d %H:%M"))
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut markdown = format!("# {}\n\n*{}*\n", self.title, self.header());
+        if !self.tags.is_empty() {
+            markdown.push_str(&format!("\nTags: #{}\n", self.tags.join(" #")));
+        }
+
+        if let Some(resume) = &self.resume {
+            markdown.push_str(&format!("\n## Resume\n\n{}\n", resume.trim()));
+        }
+
+        markdown.push_str("\n## Conversation\n");
+        for message in &self.messages {
+            let details = Self::details(message);
+            if details.is_empty() {
+                markdown.push_str(&format!("\n**{}**:\n\n", Self::speaker(message)));
+            } else {
+                markdown.push_str(&format!("\n**{}** ({}):\n\n", Self::speaker(message), details));
+            }
+            markdown.push_str(message.content.trim());
+            markdown.push('\n');
+
+            if !message.sources.is_empty() {
+                markdown.push_str("\nSources:\n");
+                for source in &message.sources {
+                    markdown.push_str(&format!("- <{}>\n", source));
+                }
+            }
+        }
+        markdown
+    }
+
+    fn to_html(&self) -> String {
+        let mut body = format!(
+            "<h1>{}</h1>\n<p class=\"details\">{}</p>\n",
+            escape(&self.title),
+            escape(&self.header())
+        );
+        if !self.tags.is_empty() {
+            body.push_str(&format!("<p class=\"tags\">#{}</p>\n", escape(&self.tags.join(" #"))));
+        }
+
+        if let Some(resume) = &self.resume {
+            body.push_str(&format!("<h2>Resume</h2>\n<div class=\"resume\">{}</div>\n", content_html(resume)));
+        }
+
+        body.push_str("<h2>Conversation</h2>\n");
+        for message in &self.messages {
+            body.push_str(&format!(
+                "<div class=\"message {}\">\n<p class=\"speaker\">{} <span class=\"details\">{}</span></p>\n{}",
+                escape(&message.role),
+                Self::speaker(message),
+                escape(&Self::details(message)),
+                content_html(&message.content)
+            ));
+
+            if !message.sources.is_empty() {
+                body.push_str("<p class=\"sources\">Sources:</p>\n<ul>\n");
+                for source in &message.sources {
+                    body.push_str(&format!("<li><a href=\"{0}\">{0}</a></li>\n", escape(source)));
+                }
+                body.push_str("</ul>\n");
+            }
+            body.push_str("</div>\n");
+        }
+
+        format!(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+            escape(&self.title),
+            STYLE,
+            body
+        )
+    }
+}
+
+// Style of the HTML export, it's inlined so the file can be shared alone
+const STYLE: &str = "
+body { font-family: sans-serif; max-width: 50em; margin: auto; padding: 1em; }
+.details { color: #777; font-size: 0.9em; }
+.tags { color: #07a; }
+.message { border-radius: 0.5em; padding: 0.5em 1em; margin: 1em 0; }
+.user { background: #eef4ff; }
+.assistant { background: #f4f4f4; }
+.speaker { font-weight: bold; }
+.text { white-space: pre-wrap; }
+pre { background: #222; color: #eee; padding: 0.8em; overflow-x: auto; }
+";
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Message content in HTML, fenced code blocks become `pre` blocks and the rest keeps its lines
+fn content_html(content: &str) -> String {
+    let mut html = String::new();
+    let mut block = String::new();
+    let mut in_code = false;
+
+    let mut flush = |block: &mut String, in_code: bool| {
+        if !block.trim().is_empty() {
+            if in_code {
+                html.push_str(&format!("<pre><code>{}</code></pre>\n", escape(block)));
+            } else {
+                html.push_str(&format!("<p class=\"text\">{}</p>\n", escape(block.trim())));
+            }
+        }
+        block.clear();
+    };
+
+    for line in content.lines() {
+        if line.trim_start().starts_with("```") {
+            flush(&mut block, in_code);
+            in_code = !in_code;
+            continue;
+        }
+        block.push_str(line);
+        block.push('\n');
+    }
+    flush(&mut block, in_code);
+    html
+}
+
+// Render a saved conversation, the result is written to `request.output` if given
+pub fn export(conv_id: &Uuid, request: &ExportRequest) -> Result<String> {
+    let rendered = ExportedConv::load(conv_id, request)?.render(request.format)?;
+
+    if let Some(path) = &request.output {
+        if let Some(dir) = std::path::Path::new(path).parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|e| Error::Storage(format!("{}: {}", dir.display(), e)))?;
+        }
+        fs::write(path, &rendered).map_err(|e| Error::Storage(format!("{}: {}", path, e)))?;
+    }
+    Ok(rendered)
+}
+
+// Path used by the UI when no output is given
+pub fn default_output(conv_id: &Uuid, format: Format) -> String {
+    format!("{}/{}.{}", EXPORT_DIR, conv_id, format.extension())
+}
@@ -0,0 +1,419 @@
+use crate::app::export::{ExportedConv, JSON_FORMAT};
+use crate::app::llm::{Message, MessageType};
+use crate::app::storage::init as storage;
+use crate::error::{Error, Result};
+use chrono::{DateTime, Local};
+use regex::Regex;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs;
+use uuid::Uuid;
+
+// Tool which made the file imported
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Source {
+    ChatGpt,   // `conversations.json` of a ChatGPT data export, or a list of OpenAI messages
+    OpenWebUi, // Chats exported from Open WebUI
+    Markdown,  // Transcript with a `role: content` line starting each message
+    Nai,       // JSON export of Néo AI
+}
+
+impl Source {
+    pub fn parse(name: &str) -> Result<Source> {
+        match name {
+            "chatgpt" | "openai" => Ok(Source::ChatGpt),
+            "openwebui" => Ok(Source::OpenWebUi),
+            "md" | "markdown" => Ok(Source::Markdown),
+            "nai" => Ok(Source::Nai),
+            _ => Err(Error::Config(format!(
+                "unknown import format {}, use chatgpt, openwebui, markdown or nai",
+                name
+            ))),
+        }
+    }
+
+    // Tag given to the imported conversations
+    fn tag(&self) -> &'static str {
+        match self {
+            Source::ChatGpt => "chatgpt",
+            Source::OpenWebUi => "openwebui",
+            Source::Markdown => "markdown",
+            Source::Nai => "nai",
+        }
+    }
+
+    // Guess the format from the content of the file
+    fn detect(contents: &str) -> Source {
+        let Ok(json) = serde_json::from_str::<Value>(contents) else {
+            return Source::Markdown;
+        };
+
+        let first = match &json {
+            Value::Array(items) => items.first().cloned().unwrap_or(Value::Null),
+            json => json.clone(),
+        };
+        if first["format"] == JSON_FORMAT {
+            Source::Nai
+        } else if first.get("chat").is_some() {
+            Source::OpenWebUi
+        } else {
+            Source::ChatGpt
+        }
+    }
+}
+
+// A conversation read from another tool, before it's saved
+struct ImportedConv {
+    id: Option<Uuid>, // Id in the other tool, kept when it's an uuid so a second import is skipped
+    title: Option<String>,
+    messages: Vec<Message>,
+}
+
+// Conversation saved by `import_file`
+pub struct ImportedSummary {
+    pub id: Uuid,
+    pub title: String,
+    pub nb_messages: usize,
+}
+
+// Only the discussion is imported, the system prompts and tool calls of the other tool don't
+// mean anything for Néo AI
+fn role(name: &str) -> Option<MessageType> {
+    match name.to_lowercase().as_str() {
+        "user" | "you" | "human" => Some(MessageType::USER),
+        "assistant" | "ai" | "néo ai" | "neo ai" | "bot" => Some(MessageType::ASSISTANT),
+        _ => None,
+    }
+}
+
+fn message(role: MessageType, content: String, timestamp: Option<DateTime<Local>>, model: Option<String>) -> Message {
+    let mut message = Message::new(role, content);
+    message.meta.timestamp = timestamp;
+    message.meta.model = model;
+    message
+}
+
+// Dates are seconds since epoch in the exports, sometimes milliseconds in Open WebUI
+fn date(value: &Value) -> Option<DateTime<Local>> {
+    let seconds = value.as_f64()?;
+    let seconds = if seconds > 1e11 { seconds / 1000.0 } else { seconds };
+    DateTime::from_timestamp(seconds as i64, 0).map(|date| date.with_timezone(&Local))
+}
+
+fn string(value: &Value) -> Option<String> {
+    value.as_str().map(|s| s.trim().to_string()).filter(|s| !s.is_empty())
+}
+
+// The file may hold one conversation or a list of them
+fn items(json: Value) -> Vec<Value> {
+    match json {
+        Value::Array(items) => items,
+        json => vec![json],
+    }
+}
+
+// Follow the `parent` links from the last message to the first one, exports keep every branch
+// of a conversation but only the one displayed is imported
+fn branch(nodes: &serde_json::Map<String, Value>, last: &str, parent_key: &str) -> Vec<Value> {
+    let mut branch = Vec::new();
+    let mut seen = HashSet::new();
+    let mut current = Some(last.to_string());
+
+    while let Some(id) = current.filter(|id| seen.insert(id.clone())) {
+        let Some(node) = nodes.get(&id) else {
+            break;
+        };
+        branch.push(node.clone());
+        current = node[parent_key].as_str().map(|id| id.to_string());
+    }
+    branch.reverse();
+    branch
+}
+
+fn parse_chatgpt(json: Value) -> Vec<ImportedConv> {
+    items(json)
+        .into_iter()
+        .map(|conv| {
+            let messages = match (conv["mapping"].as_object(), conv["current_node"].as_str()) {
+                (Some(mapping), Some(last)) => branch(mapping, last, "parent")
+                    .iter()
+                    .filter_map(|node| {
+                        let node = &node["message"];
+                        let role = role(node["author"]["role"].as_str()?)?;
+                        let parts: Vec<&str> = node["content"]["parts"]
+                            .as_array()?
+                            .iter()
+                            .filter_map(|part| part.as_str())
+                            .collect();
+                        let content = string(&Value::String(parts.join("\n")))?;
+                        let model = string(&node["metadata"]["model_slug"]);
+                        Some(message(role, content, date(&node["create_time"]), model))
+                    })
+                    .collect(),
+                // Plain list of OpenAI chat messages
+                _ => conv["messages"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|m| Some(message(role(m["role"].as_str()?)?, string(&m["content"])?, None, None)))
+                    .collect(),
+            };
+
+            ImportedConv {
+                id: conv["id"].as_str().and_then(|id| Uuid::parse_str(id).ok()),
+                title: string(&conv["title"]),
+                messages,
+            }
+        })
+        .collect()
+}
+
+fn parse_openwebui(json: Value) -> Vec<ImportedConv> {
+    items(json)
+        .into_iter()
+        .map(|item| {
+            let chat = &item["chat"];
+            let history = &chat["history"];
+            let nodes = match (history["messages"].as_object(), history["currentId"].as_str()) {
+                (Some(nodes), Some(last)) => branch(nodes, last, "parentId"),
+                _ => chat["messages"].as_array().cloned().unwrap_or_default(),
+            };
+
+            let messages = nodes
+                .iter()
+                .filter_map(|m| {
+                    let role = role(m["role"].as_str()?)?;
+                    let model = string(&m["model"]).or_else(|| m["models"][0].as_str().map(|s| s.to_string()));
+                    Some(message(role, string(&m["content"])?, date(&m["timestamp"]), model))
+                })
+                .collect();
+
+            ImportedConv {
+                id: item["id"].as_str().and_then(|id| Uuid::parse_str(id).ok()),
+                title: string(&item["title"]).or_else(|| string(&chat["title"])),
+                messages,
+            }
+        })
+        .collect()
+}
+
+fn parse_nai(json: Value) -> Result<Vec<ImportedConv>> {
+    items(json)
+        .into_iter()
+        .map(|item| {
+            let conv: ExportedConv = serde_json::from_value(item)?;
+            let messages = conv
+                .messages
+                .into_iter()
+                .filter_map(|m| {
+                    let mut message = message(role(&m.role)?, m.content, m.timestamp, m.model);
+                    message.meta.route = m.route;
+                    message.meta.sources = m.sources;
+                    Some(message)
+                })
+                .collect();
+            Ok(ImportedConv { id: Some(conv.id), title: Some(conv.title), messages })
+        })
+        .collect()
+}
+
+// A line starting with `role:` (optionally in bold, like `**user**:`) starts a new message, the
+// other lines continue the current one. A `# ` heading before the first message is the title
+fn parse_markdown(contents: &str) -> Vec<ImportedConv> {
+    // `**Néo AI** (2026-10-18 07:58, chat, llama3.2):` as written by the Markdown export, the
+    // details may contain colons so the content starts after the last `):`
+    let header = Regex::new(r"^\*{0,2}([^*:(]+?)\*{0,2}(?: \(.*\))?:(.*)$").unwrap();
+
+    let mut title = None;
+    let mut messages: Vec<Message> = Vec::new();
+
+    for line in contents.lines() {
+        let start = header
+            .captures(line)
+            .and_then(|captures| Some((role(captures[1].trim())?, captures.get(2)?.as_str())));
+
+        match start {
+            Some((role, content)) => messages.push(Message::new(role, content.trim().to_string())),
+            None => match messages.last_mut() {
+                Some(message) => {
+                    message.content.push('\n');
+                    message.content.push_str(line);
+                }
+                None => {
+                    if let Some(heading) = line.strip_prefix("# ") {
+                        title = Some(heading.trim().to_string());
+                    }
+                }
+            },
+        }
+    }
+
+    for message in messages.iter_mut() {
+        message.content = message.content.trim().to_string();
+    }
+    messages.retain(|message| !message.content.is_empty());
+
+    vec![ImportedConv { id: None, title, messages }]
+}
+
+// Save the conversations of a file exported by another tool, the format is guessed when not
+// given. Conversations already imported (same id) and empty ones are skipped
+pub fn import_file(path: &str, source: Option<Source>) -> Result<Vec<ImportedSummary>> {
+    let contents = fs::read_to_string(path).map_err(|e| Error::Storage(format!("{}: {}", path, e)))?;
+    let source = source.unwrap_or_else(|| Source::detect(&contents));
+
+    let convs = match source {
+        Source::Markdown => parse_markdown(&contents),
+        source => {
+            let json: Value = serde_json::from_str(&contents)?;
+            match source {
+                Source::ChatGpt => parse_chatgpt(json),
+                Source::OpenWebUi => parse_openwebui(json),
+                _ => parse_nai(json)?,
+            }
+        }
+    };
+
+    let storage = storage::open()?;
+    let existing = storage.ids()?;
+
+    let mut imported = Vec::new();
+    for conv in convs.into_iter().filter(|conv| !conv.messages.is_empty()) {
+        if conv.id.is_some_and(|id| existing.contains(&id)) {
+            continue;
+        }
+
+        let id = conv.id.unwrap_or_else(Uuid::new_v4);
+        storage.import(&id, &conv.messages)?;
+        if let Some(title) = &conv.title {
+            storage.rename(&id, title)?;
+        }
+        storage.set_tags(&id, &[source.tag().to_string()])?;
+
+        imported.push(ImportedSummary {
+            id,
+            title: conv.title.unwrap_or_else(|| "(untitled)".to_string()),
+            nb_messages: conv.messages.len(),
+        });
+    }
+    Ok(imported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::export::{ExportedMessage, Format, JSON_VERSION};
+    use serde_json::json;
+
+    fn contents(conv: &ImportedConv) -> Vec<(String, String)> {
+        conv.messages.iter().map(|m| (m.role.to_string(), m.content.clone())).collect()
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(role, content)| (role.to_string(), content.to_string())).collect()
+    }
+
+    #[test]
+    fn chatgpt_follows_the_current_branch() {
+        let json = json!([{
+            "id": "6f9619ff-8b86-d011-b42d-00cf4fc964ff",
+            "title": "Verdun",
+            "current_node": "c",
+            "mapping": {
+                "root": { "message": null, "parent": null },
+                "system": { "message": { "author": { "role": "system" }, "content": { "parts": ["hidden"] } }, "parent": "root" },
+                "a": { "message": { "author": { "role": "user" }, "content": { "parts": ["Tell me about Verdun"] }, "create_time": 1700000000.5 }, "parent": "system" },
+                "old": { "message": { "author": { "role": "assistant" }, "content": { "parts": ["Old answer"] } }, "parent": "a" },
+                "c": { "message": { "author": { "role": "assistant" }, "content": { "parts": ["The battle"] }, "metadata": { "model_slug": "gpt-4o" } }, "parent": "a" }
+            }
+        }]);
+
+        let convs = parse_chatgpt(json);
+        assert_eq!(convs.len(), 1);
+        assert_eq!(convs[0].title.as_deref(), Some("Verdun"));
+        assert!(convs[0].id.is_some());
+        assert_eq!(contents(&convs[0]), pairs(&[("user", "Tell me about Verdun"), ("assistant", "The battle")]));
+        assert_eq!(convs[0].messages[1].meta.model.as_deref(), Some("gpt-4o"));
+        assert!(convs[0].messages[0].meta.timestamp.is_some());
+    }
+
+    #[test]
+    fn chatgpt_reads_plain_message_lists() {
+        let json = json!({ "messages": [
+            { "role": "system", "content": "Be nice" },
+            { "role": "user", "content": "Hi" },
+            { "role": "assistant", "content": "Hello" }
+        ]});
+
+        let convs = parse_chatgpt(json);
+        assert_eq!(contents(&convs[0]), pairs(&[("user", "Hi"), ("assistant", "Hello")]));
+        assert!(convs[0].id.is_none());
+    }
+
+    #[test]
+    fn openwebui_follows_the_history() {
+        let json = json!([{
+            "id": "not an uuid",
+            "title": "Treaty",
+            "chat": { "history": { "currentId": "b2", "messages": {
+                "a": { "role": "user", "content": "Which treaty?", "parentId": null, "timestamp": 1700000000000u64 },
+                "b1": { "role": "assistant", "content": "First try", "parentId": "a" },
+                "b2": { "role": "assistant", "content": "Versailles", "parentId": "a", "models": ["llama3.2"] }
+            }}}
+        }]);
+
+        let convs = parse_openwebui(json);
+        assert_eq!(convs[0].title.as_deref(), Some("Treaty"));
+        assert!(convs[0].id.is_none());
+        assert_eq!(contents(&convs[0]), pairs(&[("user", "Which treaty?"), ("assistant", "Versailles")]));
+        assert_eq!(convs[0].messages[1].meta.model.as_deref(), Some("llama3.2"));
+        // Milliseconds are read as a date of 2023, not of the far future
+        assert_eq!(convs[0].messages[0].meta.timestamp.map(|date| date.timestamp()), Some(1700000000));
+    }
+
+    #[test]
+    fn markdown_reads_roles_and_continuation_lines() {
+        let convs = parse_markdown("# Verdun\n\nuser: Tell me\nabout Verdun\n\n**assistant**: A battle\nNote: in 1916\n");
+        assert_eq!(convs[0].title.as_deref(), Some("Verdun"));
+        assert_eq!(
+            contents(&convs[0]),
+            pairs(&[("user", "Tell me\nabout Verdun"), ("assistant", "A battle\nNote: in 1916")])
+        );
+    }
+
+    #[test]
+    fn markdown_export_round_trips() {
+        let date = DateTime::from_timestamp(1760000000, 0).unwrap().with_timezone(&Local);
+        let message = |role: &str, content: &str, route: Option<&str>| ExportedMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+            timestamp: Some(date),
+            route: route.map(|route| route.to_string()),
+            model: route.map(|_| "llama3.2".to_string()),
+            sources: Vec::new(),
+        };
+        let conv = ExportedConv {
+            format: JSON_FORMAT.to_string(),
+            version: JSON_VERSION,
+            id: Uuid::new_v4(),
+            title: "Verdun: the battle".to_string(),
+            tags: vec!["history".to_string()],
+            exported: date,
+            resume: Some("- A battle".to_string()),
+            messages: vec![
+                message("user", "Tell me about Verdun", None),
+                message("assistant", "The treaty: 1919\n\n```\nkey: value\n```", Some("wikipedia")),
+            ],
+        };
+
+        let convs = parse_markdown(&conv.render(Format::Markdown).unwrap());
+        assert_eq!(convs[0].title.as_deref(), Some("Verdun: the battle"));
+        assert_eq!(
+            contents(&convs[0]),
+            pairs(&[
+                ("user", "Tell me about Verdun"),
+                ("assistant", "The treaty: 1919\n\n```\nkey: value\n```"),
+            ])
+        );
+    }
+}
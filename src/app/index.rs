@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+
+// BM25 parameters, the usual values
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+// Inverted index ranking documents of any kind with BM25, each one is indexed from a text
+pub struct InvertedIndex<D> {
+    documents: Vec<D>,
+    lengths: Vec<usize>,                            // Number of terms of each document
+    postings: HashMap<String, Vec<(usize, usize)>>, // Term to (document, term frequency)
+    total_length: usize,
+}
+
+// Split a text in lowercase words
+pub fn terms(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(|term| term.to_lowercase())
+        .collect()
+}
+
+impl<D> InvertedIndex<D> {
+    pub fn new() -> InvertedIndex<D> {
+        InvertedIndex {
+            documents: Vec::new(),
+            lengths: Vec::new(),
+            postings: HashMap::new(),
+            total_length: 0,
+        }
+    }
+
+    pub fn add(&mut self, document: D, text: &str) {
+        let id = self.documents.len();
+        let terms = terms(text);
+        self.lengths.push(terms.len());
+        self.total_length += terms.len();
+
+        let mut frequencies: HashMap<String, usize> = HashMap::new();
+        for term in terms {
+            *frequencies.entry(term).or_insert(0) += 1;
+        }
+        for (term, frequency) in frequencies {
+            self.postings.entry(term).or_default().push((id, frequency));
+        }
+
+        self.documents.push(document);
+    }
+
+    // Documents matching at least one word of the query with their score, the best ranked first
+    pub fn search(&self, query: &str) -> Vec<(&D, f64)> {
+        let nb_documents = self.documents.len() as f64;
+        let average_length = self.total_length as f64 / self.documents.len().max(1) as f64;
+
+        let mut scores: HashMap<usize, f64> = HashMap::new();
+        for term in terms(query) {
+            let Some(postings) = self.postings.get(&term) else {
+                continue;
+            };
+
+            let idf = ((nb_documents - postings.len() as f64 + 0.5) / (postings.len() as f64 + 0.5) + 1.0).ln();
+            for (id, frequency) in postings {
+                let frequency = *frequency as f64;
+                let length = self.lengths[*id] as f64 / average_length.max(1.0);
+                let score = idf * frequency * (K1 + 1.0) / (frequency + K1 * (1.0 - B + B * length));
+                *scores.entry(*id).or_insert(0.0) += score;
+            }
+        }
+
+        let mut ranked: Vec<(&D, f64)> = scores.into_iter().map(|(id, score)| (&self.documents[id], score)).collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        ranked
+    }
+}
+
+impl<D> Default for InvertedIndex<D> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn documents_with_the_rarer_terms_rank_first() {
+        let mut index = InvertedIndex::new();
+        index.add(1, "the cat sat on the mat");
+        index.add(2, "the dog sat on the log");
+        index.add(3, "nothing in common");
+
+        let ranked: Vec<i32> = index.search("Cat, sat").into_iter().map(|(id, _)| *id).collect();
+        assert_eq!(ranked, [1, 2]);
+        assert!(index.search("bird").is_empty());
+    }
+}
@@ -1,79 +1,813 @@
-use crate::app::llm::{Message, MessageType, LLM};
-use crate::app::modules::{wikipedia, resume, chat, code};
+use crate::app::compare::{self, CompareConfig, Comparison};
+use crate::app::context::ContextUsage;
+use crate::app::conversation;
+use crate::app::export::{self, ExportRequest};
+use crate::app::importer;
+use crate::app::llm::{Answer, Message, MessageType, StreamUpdate, TokenSender, LLM};
+use crate::app::modules::{wikipedia, resume, chat, code, title};
+use crate::app::project::{self, Project};
+use crate::app::sandbox::{self, RunOutput};
+use crate::app::tree::ConvTree;
+use crate::error::{Error, Result};
+use crate::helper::clipboard;
 use crate::helper::init::warn;
+use chrono::Local;
+use std::sync::Arc;
+use std::time::Instant;
 use uuid::Uuid;
-use tokio::runtime::Builder;
+use tokio::runtime::{Builder, Runtime};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+// Marker appended to an answer stopped by the user
+const CANCELLED_MARKER: &str = "[cancelled]";
+
+// Events sent by the pipeline running in the background to the App
+pub enum AppEvent {
+    Token(String), // New token of the answer being generated
+    Context(ContextUsage), // Size of the prompt sent to the LLM
+    Trace(String), // Step of the pipeline, shown but not saved
+    Restart, // The answer is generated again
+    Done(Box<Result<Answer>>), // Complete answer or error
+    Alternative(usize, String), // New token of an alternative answer being compared
+    AlternativeDone(usize, Box<Result<(Answer, i64)>>), // Complete alternative and its seed, or error
+}
 
 pub struct App {
-    pub messages: Vec<Message>, // History of recorded message
+    pub messages: Vec<Message>, // Branch of the conversation shown, with the notices
+    tree: ConvTree, // Every saved message of the conversation, all branches included
     pub conv_id: Uuid, // ID for retrieving and saving the history of messag
-    categorize_llm: LLM,
+    pub title: Option<String>, // Title of the conversation, given or generated
+    pub pending: Option<Message>, // Answer being generated, saved once complete
+    pub context: Option<ContextUsage>, // Context window used by the last request
+    pub comparison: Option<Comparison>, // Alternative answers shown side by side
+    pub project: Option<Arc<Project>>, // Project the code questions are about, with its index
+    categorize_llm: Arc<LLM>,
+    runtime: Runtime, // Runtime running the LLM pipelines off the UI thread
+    task: Option<JoinHandle<()>>, // Pipeline running in background
+    events_rx: Option<UnboundedReceiver<AppEvent>>, // Events of the running pipeline
+    title_task: Option<(Uuid, oneshot::Receiver<Result<String>>)>, // Title being generated for a conversation
+    run_task: Option<oneshot::Receiver<Result<(usize, RunOutput)>>>, // Code block running, with its number
 }
 
 impl App {
-    pub fn new() -> App {
+    pub fn new() -> Result<App> {
+        let runtime = Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .map_err(|e| Error::Runtime(e.to_string()))?;
 
-        App {
+        Ok(App {
             messages: Vec::new(),
+            tree: ConvTree::default(),
             conv_id: Uuid::new_v4(),
-            categorize_llm: LLM::new("config/categorize-LLM.json"),
-        }
+            title: None,
+            pending: None,
+            context: None,
+            comparison: None,
+            project: None,
+            categorize_llm: Arc::new(LLM::new("config/categorize-LLM.json")?),
+            runtime,
+            task: None,
+            events_rx: None,
+            title_task: None,
+            run_task: None,
+        })
     }
 
-    fn append_message(&mut self, msg: String, role: MessageType) {
-        let message = Message::new(role, msg);
+    fn append_message(&mut self, mut message: Message) {
+        if message.meta.timestamp.is_none() {
+            message.meta.timestamp = Some(Local::now());
+        }
 
-        let err = message.save_message(self.conv_id.to_string());
-        //warn(err.is_err().to_string()); 
+        // The message follows the last saved message shown, which isn't the last one saved
+        // when another branch is shown
+        let previous = self.messages.iter().rev().find_map(|m| m.position);
+        if message.meta.edit_of.is_none() && previous.is_some() && previous != self.tree.len().checked_sub(1) {
+            message.meta.parent = previous;
+        }
+
+        match message.save_message(&self.conv_id) {
+            Ok(()) => {
+                message.position = Some(self.tree.push(message.clone()));
+                // A title given before the first message is saved with it
+                if let (1, Some(title)) = (self.tree.len(), &self.title) {
+                    if let Err(e) = conversation::rename(&self.conv_id, title) {
+                        warn(e.to_string());
+                    }
+                }
+            }
+            Err(e) => {
+                warn(e.to_string());
+                self.show_error(e);
+            }
+        }
 
         self.messages.push(message);
     }
 
-    fn categorize_ask(&mut self) {
-        let runtime = Builder::new_current_thread().enable_all().build().unwrap();
+    // Show a notice to the user as a system message, it isn't saved nor sent to the LLM
+    pub fn notify(&mut self, text: String) {
+        self.messages.push(Message::new(MessageType::SYSTEM, text));
+    }
+
+    fn show_error(&mut self, error: Error) {
+        self.notify(error.to_string());
+    }
+
+    // True while a pipeline is running, a new request can't be sent until it's done
+    pub fn is_busy(&self) -> bool {
+        self.pending.is_some() || self.run_task.is_some() || self.comparison.as_ref().is_some_and(|c| c.is_running())
+    }
+
+    // True while the conversation can't be changed: a pipeline is running, or alternatives are
+    // shown over the messages until one is picked or the comparison is closed
+    pub fn is_locked(&self) -> bool {
+        self.is_busy() || self.comparison.is_some()
+    }
+
+    // Start the pipeline in background, `mode` is the module to use or None to let the
+    // categorize LLM choose it
+    fn spawn_pipeline(&mut self, mode: Option<&'static str>) {
+        self.pending = Some(Message::new(MessageType::ASSISTANT, String::new()));
+
+        // System messages are errors shown to the user, not part of the conversation
+        let messages: Vec<Message> = self.messages
+            .iter()
+            .filter(|m| !matches!(m.role, MessageType::SYSTEM))
+            .cloned()
+            .collect();
+        let categorize_llm = Arc::clone(&self.categorize_llm);
+        let project = self.project.clone();
+
+        // Each pipeline has its own channel, so a cancelled one can't send events anymore
+        let (events_tx, events_rx) = unbounded_channel();
+        self.events_rx = Some(events_rx);
+
+        let task = self.runtime.spawn(async move {
+            let (tx, mut rx) = unbounded_channel::<StreamUpdate>();
+
+            // The token sender is moved inside the pipeline, so the stream ends with it
+            let pipeline = async move {
+                let start = Instant::now();
+                let mode = match mode {
+                    Some(mode) => mode.to_string(),
+                    None => categorize(&categorize_llm, &messages).await?,
+                };
+
+                let mut answer = ask(&mode, messages, project, tx).await?;
+                answer.meta.route = Some(mode);
+                answer.meta.duration_ms = Some(start.elapsed().as_millis() as u64);
+                Ok(answer)
+            };
+
+            let stream = async {
+                while let Some(update) = rx.recv().await {
+                    let event = match update {
+                        StreamUpdate::Token(token) => AppEvent::Token(token),
+                        StreamUpdate::Context(usage) => AppEvent::Context(usage),
+                        StreamUpdate::Trace(step) => AppEvent::Trace(step),
+                        StreamUpdate::Restart => AppEvent::Restart,
+                    };
+                    let _ = events_tx.send(event);
+                }
+            };
+
+            let (result, _) = tokio::join!(pipeline, stream);
+            let _ = events_tx.send(AppEvent::Done(Box::new(result)));
+        });
+        self.task = Some(task);
+    }
+
+    // Stop the running pipeline, dropping the task aborts the HTTP requests in progress. The
+    // partial answer is kept with a marker so the conversation stays consistent
+    pub fn cancel(&mut self) {
+        // Apply the tokens received before the cancellation
+        self.update();
+
+        if let Some(task) = self.task.take() {
+            task.abort();
+        }
+        self.events_rx = None;
+
+        // The process is killed by its own timeout, its output is dropped
+        if self.run_task.take().is_some() {
+            self.notify("Run cancelled".to_string());
+        }
+
+        if let Some(partial) = self.pending.take() {
+            let content = if partial.content.is_empty() {
+                CANCELLED_MARKER.to_string()
+            } else {
+                format!("{}\n{}", partial.content, CANCELLED_MARKER)
+            };
+            self.append_message(Message::new(MessageType::ASSISTANT, content));
+        }
+
+        // Alternatives aren't saved, the partial ones can still be read
+        if let Some(comparison) = self.comparison.as_mut() {
+            for alternative in comparison.alternatives.iter_mut().filter(|a| !a.done) {
+                alternative.done = true;
+                alternative.message.content.push_str(CANCELLED_MARKER);
+            }
+        }
+    }
+
+    // Apply the events received from the background pipeline, called on every UI tick
+    pub fn update(&mut self) {
+        self.update_title();
+        self.update_run();
 
-        let result = runtime.block_on(async {
-            // Ask the LLM to categorise the request between (chat, code, wikipedia)
-            self.categorize_llm.ask_tools(&self.messages).await
+        while let Some(event) = self.events_rx.as_mut().and_then(|rx| rx.try_recv().ok()) {
+            match event {
+                AppEvent::Token(token) => {
+                    if let Some(partial) = self.pending.as_mut() {
+                        partial.content.push_str(&token);
+                    }
+                }
+                AppEvent::Context(usage) => self.context = Some(usage),
+                AppEvent::Trace(step) => self.notify(step),
+                AppEvent::Restart => {
+                    if let Some(partial) = self.pending.as_mut() {
+                        partial.content.clear();
+                    }
+                }
+                // Only the complete answer is saved
+                AppEvent::Done(result) => {
+                    self.pending = None;
+                    self.task = None;
+                    self.events_rx = None;
+                    match *result {
+                        Ok(answer) => {
+                            let message = Message::from_answer(answer);
+                            let resume = resume::is_resume(&message).then(|| message.content.clone());
+                            self.append_message(message);
+                            if let Some(resume) = resume {
+                                self.save_resume(&resume);
+                            }
+                            self.make_title();
+                        }
+                        Err(e) => self.show_error(e),
+                    }
+                }
+                AppEvent::Alternative(k, token) => {
+                    if let Some(alternative) = self.comparison.as_mut().and_then(|c| c.alternatives.get_mut(k)) {
+                        alternative.message.content.push_str(&token);
+                    }
+                }
+                AppEvent::AlternativeDone(k, result) => {
+                    if let Some(alternative) = self.comparison.as_mut().and_then(|c| c.alternatives.get_mut(k)) {
+                        alternative.done = true;
+                        match *result {
+                            Ok((answer, seed)) => {
+                                alternative.label = format!("{} (seed {})", alternative.label, seed);
+                                alternative.message = Message::from_answer(answer);
+                                alternative.message.meta.route = Some("chat".to_string());
+                            }
+                            Err(e) => alternative.message.content = e.to_string(),
+                        }
+                    }
+                    if !self.is_busy() {
+                        self.task = None;
+                        self.events_rx = None;
+                    }
+                }
+            }
+        }
+    }
+
+    // Generate a title in background once the conversation has an answer, if it has none
+    fn make_title(&mut self) {
+        if self.title.is_some() || self.title_task.is_some() {
+            return;
+        }
+
+        let messages: Vec<Message> = self.messages
+            .iter()
+            .filter(|m| !matches!(m.role, MessageType::SYSTEM))
+            .cloned()
+            .collect();
+        let (title_tx, title_rx) = oneshot::channel();
+        self.runtime.spawn(async move {
+            let _ = title_tx.send(title::make_title(messages).await);
         });
+        self.title_task = Some((self.conv_id, title_rx));
+    }
+
+    // Save the generated title, unless the conversation was renamed in the meantime
+    fn update_title(&mut self) {
+        let Some((conv_id, title_rx)) = self.title_task.as_mut() else {
+            return;
+        };
+        let result = match title_rx.try_recv() {
+            Ok(result) => result,
+            Err(oneshot::error::TryRecvError::Empty) => return,
+            Err(oneshot::error::TryRecvError::Closed) => Err(Error::Parse("title not generated".to_string())),
+        };
+        let conv_id = *conv_id;
+        self.title_task = None;
 
+        // A missing title isn't worth bothering the user, the first message is used instead
         match result {
-            Ok(msg) => {
-                let categorie = msg[0]["function"]["arguments"]["category_choice"].clone();
-                self.ask(&categorie.to_string().replace("\"", ""));
-            },
-            Err(e) => self.append_message(e.to_string(), MessageType::ASSISTANT),
+            Ok(title) if conversation::title(&conv_id).is_none() => {
+                if let Err(e) = conversation::rename(&conv_id, &title) {
+                    warn(e.to_string());
+                } else if conv_id == self.conv_id {
+                    self.title = Some(title);
+                }
+            }
+            Ok(_) => {}
+            Err(e) => warn(format!("Title of {}: {}", conv_id, e)),
         }
     }
 
-    fn ask(&mut self, mode: &str) {
-        warn(format!("Categorie: {}", mode));
-        let runtime = Builder::new_current_thread()
-            .enable_all()
-            .build().unwrap();
+    // Run a code block of the answer shown at `index`, or of the last answer. `number` counts
+    // the blocks from 1, the first one which can be run is used when it's not given. The output
+    // is added to the conversation as a system message once the process ends
+    pub fn run_code(&mut self, index: Option<usize>, number: Option<usize>) {
+        if self.is_busy() {
+            return;
+        }
 
-        let result = runtime.block_on(async {
-            if mode == "resume" {
-                resume::resume_conv(self.messages.clone()).await
-            } else if mode == "wikipedia" {
-                wikipedia::ask_wiki(&self.messages).await
+        let is_answer = |m: &Message| matches!(m.role, MessageType::ASSISTANT);
+        let answer = match index {
+            Some(index) => self.messages.get(index).filter(|m| is_answer(m)),
+            None => self.messages.iter().rfind(|m| is_answer(m)),
+        };
+        let Some(answer) = answer else {
+            self.notify("Select an answer of Néo AI to run its code".to_string());
+            return;
+        };
+
+        let blocks = code::code_blocks(&answer.content);
+        let config = match sandbox::SandboxConfig::load() {
+            Ok(config) => config,
+            Err(e) => {
+                self.show_error(e);
+                return;
+            }
+        };
+        let block = match number {
+            Some(number) => number.checked_sub(1).and_then(|i| blocks.get(i)).map(|block| (number, block)),
+            None => blocks
+                .iter()
+                .enumerate()
+                .find(|(_, block)| config.language(block).is_some())
+                .map(|(i, block)| (i + 1, block)),
+        };
+        let Some((number, block)) = block else {
+            self.notify(format!("No code block to run, the answer has {} blocks", blocks.len()));
+            return;
+        };
+
+        let block = block.clone();
+        let (run_tx, run_rx) = oneshot::channel();
+        self.runtime.spawn_blocking(move || {
+            let _ = run_tx.send(sandbox::run(&block).map(|output| (number, output)));
+        });
+        self.run_task = Some(run_rx);
+    }
+
+    // List the code blocks of the message shown at `index`, or of the last answer. With a block
+    // number, the block is copied to the clipboard, or written to the file given after it
+    pub fn extract_code(&mut self, index: Option<usize>, args: &str) {
+        let message = match index {
+            Some(index) => self.messages.get(index),
+            None => self.messages.iter().rfind(|m| matches!(m.role, MessageType::ASSISTANT)),
+        };
+        let Some(message) = message else {
+            self.notify("Select a message to extract its code".to_string());
+            return;
+        };
+
+        let blocks = code::code_blocks(&message.content);
+        let mut args = args.split_whitespace();
+        let Some(number) = args.next() else {
+            let list: Vec<String> = blocks
+                .iter()
+                .enumerate()
+                .map(|(i, block)| {
+                    let first = block.code.lines().find(|line| !line.trim().is_empty()).unwrap_or_default();
+                    format!(
+                        "{}. {} ({} lines): {}",
+                        i + 1,
+                        block.language.as_deref().unwrap_or("text"),
+                        block.code.lines().count(),
+                        first.trim()
+                    )
+                })
+                .collect();
+            if list.is_empty() {
+                self.notify("The message has no code block".to_string());
             } else {
-                chat::ask_chat(self.messages.clone()).await
+                self.notify(format!("Code blocks, code <n> copies one, code <n> <file> saves it:\n{}", list.join("\n")));
+            }
+            return;
+        };
+
+        let block = number.parse::<usize>().ok().and_then(|n| n.checked_sub(1)).and_then(|i| blocks.get(i));
+        let Some(block) = block else {
+            self.notify(format!("No code block {}, the message has {}", number, blocks.len()));
+            return;
+        };
+
+        let path = args.collect::<Vec<&str>>().join(" ");
+        let result = match path.as_str() {
+            "" => clipboard::copy(&block.code)
+                .map(|_| format!("Code block {} copied to the clipboard", number))
+                .map_err(|e| Error::Terminal(format!("clipboard: {}", e))),
+            path => write_new_file(path, &block.code).map(|_| format!("Code block {} written to {}", number, path)),
+        };
+        match result {
+            Ok(done) => self.notify(done),
+            Err(e) => self.show_error(e),
+        }
+    }
+
+    // Save the output of the code run, it's part of the conversation but not sent to the LLM
+    fn update_run(&mut self) {
+        let Some(run_rx) = self.run_task.as_mut() else {
+            return;
+        };
+        let result = match run_rx.try_recv() {
+            Ok(result) => result,
+            Err(oneshot::error::TryRecvError::Empty) => return,
+            Err(oneshot::error::TryRecvError::Closed) => Err(Error::Sandbox("code run stopped".to_string())),
+        };
+        self.run_task = None;
+
+        match result {
+            Ok((number, output)) => {
+                let mut message = Message::new(MessageType::SYSTEM, output.report(number));
+                message.meta.route = Some("run".to_string());
+                self.append_message(message);
+            }
+            Err(e) => self.show_error(e),
+        }
+    }
+
+    // Continue a saved conversation, the new messages are appended to its file. The running
+    // answer of the current conversation is cancelled
+    pub fn load_conv(&mut self, prefix: &str) -> Result<()> {
+        let conv_id = conversation::resolve(prefix)?;
+        // The partial answer is saved before the conversation is read, it may be the same one
+        self.cancel();
+        let tree = ConvTree::new(conversation::load(&conv_id)?);
+
+        self.comparison = None;
+        self.conv_id = conv_id;
+        self.title = conversation::title(&conv_id);
+        self.project = project::attached(&conv_id)
+            .unwrap_or_else(|e| {
+                warn(e.to_string());
+                None
+            })
+            .map(|root| Arc::new(Project::new(root)));
+        self.messages = tree.current();
+        self.tree = tree;
+        self.context = None;
+        Ok(())
+    }
+
+    // Same as `load_conv`, errors are shown in the conversation
+    pub fn open_conv(&mut self, prefix: &str) {
+        if let Err(e) = self.load_conv(prefix) {
+            self.show_error(e);
+        }
+    }
+
+    // Start a new empty conversation
+    pub fn new_conv(&mut self) {
+        self.cancel();
+        self.comparison = None;
+        self.conv_id = Uuid::new_v4();
+        self.title = None;
+        self.project = None;
+        self.messages.clear();
+        self.tree = ConvTree::default();
+        self.context = None;
+    }
+
+    // Delete a saved conversation, a new one is started if it's the current one
+    pub fn delete_conv(&mut self, conv_id: &Uuid) {
+        // The running answer is cancelled first, its partial answer would be saved after the
+        // deletion and bring the conversation back
+        if *conv_id == self.conv_id {
+            self.new_conv();
+        }
+        // A title generated later would be saved for the deleted conversation
+        if self.title_task.as_ref().is_some_and(|(id, _)| id == conv_id) {
+            self.title_task = None;
+        }
+
+        let deleted = conversation::delete(conv_id)
+            .and_then(|_| resume::delete_resume(conv_id))
+            .and_then(|_| project::detach(conv_id));
+        if let Err(e) = deleted {
+            self.show_error(e);
+        }
+    }
+
+    pub fn rename_conv(&mut self, conv_id: &Uuid, title: &str) {
+        // A new conversation isn't saved before its first message, its title waits for it
+        if *conv_id == self.conv_id && self.tree.len() == 0 {
+            self.title = Some(title.trim().to_string()).filter(|title| !title.is_empty());
+            return;
+        }
+
+        if let Err(e) = conversation::rename(conv_id, title) {
+            self.show_error(e);
+        } else if *conv_id == self.conv_id {
+            self.title = conversation::title(conv_id);
+        }
+    }
+
+    // Replace the tags of a conversation, no tags removes them
+    pub fn tag_conv(&mut self, conv_id: &Uuid, tags: &[String]) {
+        if let Err(e) = conversation::set_tags(conv_id, tags) {
+            self.show_error(e);
+        }
+    }
+
+    // Attach a project directory to the current conversation, its files are given to the code
+    // module. No directory shows the one attached
+    pub fn attach_project(&mut self, dir: &str) {
+        if dir.trim().is_empty() {
+            match &self.project {
+                Some(project) => self.notify(format!("Project attached: {}", project.root.display())),
+                None => self.notify("No project attached, use attach <directory>".to_string()),
+            }
+            return;
+        }
+
+        match project::attach(&self.conv_id, dir.trim()) {
+            Ok(root) => {
+                self.notify(format!("Project attached: {}", root.display()));
+                self.project = Some(Arc::new(Project::new(root)));
+            }
+            Err(e) => self.show_error(e),
+        }
+    }
+
+    pub fn detach_project(&mut self) {
+        match project::detach(&self.conv_id) {
+            Ok(()) => {
+                self.project = None;
+                self.notify("Project detached".to_string());
             }
+            Err(e) => self.show_error(e),
+        }
+    }
+
+    // Export the current conversation, `args` are the format and options of `ExportRequest`
+    pub fn export_conv(&mut self, args: &str) {
+        let args: Vec<&str> = args.split_whitespace().collect();
+        let result = ExportRequest::parse(&args).and_then(|mut request| {
+            let output = request
+                .output
+                .get_or_insert_with(|| export::default_output(&self.conv_id, request.format))
+                .clone();
+            export::export(&self.conv_id, &request).map(|_| output)
         });
 
         match result {
-            Ok(msg) => self.append_message(msg.to_string(), MessageType::ASSISTANT),
-            Err(e) => self.append_message(e.to_string(), MessageType::ASSISTANT),
+            Ok(output) => self.notify(format!("Conversation exported to {}", output)),
+            Err(e) => self.show_error(e),
+        }
+    }
+
+    // Import the conversations of a file exported by another tool, a single conversation is
+    // opened
+    pub fn import_conv(&mut self, path: &str) {
+        match importer::import_file(path.trim(), None) {
+            Ok(imported) if imported.len() == 1 => {
+                let conv_id = imported[0].id.to_string();
+                self.open_conv(&conv_id);
+                self.notify(format!("Conversation imported from {}", path.trim()));
+            }
+            Ok(imported) => self.notify(format!("{} conversations imported from {}", imported.len(), path.trim())),
+            Err(e) => self.show_error(e),
         }
     }
 
     pub fn send_message(&mut self, content: String) {
-        self.append_message(content, MessageType::USER);
-        self.categorize_ask();
+        if self.is_locked() {
+            return;
+        }
+
+        self.append_message(Message::new(MessageType::USER, content));
+        self.spawn_pipeline(None);
+    }
+
+    // Replace the user message shown at `index` by a new version and answer it again. The old
+    // message and what followed it stay saved as another branch
+    pub fn edit_message(&mut self, index: usize, content: String) {
+        if self.is_locked() {
+            return;
+        }
+
+        let edited = self.messages
+            .get(index)
+            .filter(|m| matches!(m.role, MessageType::USER))
+            .and_then(|m| m.position);
+        let Some(edited) = edited else {
+            self.notify("Only a saved message of yours can be edited".to_string());
+            return;
+        };
+
+        self.messages.truncate(index);
+        let mut message = Message::new(MessageType::USER, content);
+        message.meta.edit_of = Some(edited);
+        self.append_message(message);
+        self.spawn_pipeline(None);
+    }
+
+    // Versions of the message shown at `index`, as (number of the one shown, number of versions)
+    pub fn versions(&self, index: usize) -> Option<(usize, usize)> {
+        let position = self.messages.get(index)?.position?;
+        let siblings = self.tree.siblings(position);
+        let number = siblings.iter().position(|p| *p == position)?;
+        Some((number + 1, siblings.len()))
+    }
+
+    // Show the previous (`step` -1) or next (`step` 1) version of the message shown at `index`,
+    // with the most recent messages following it
+    pub fn switch_branch(&mut self, index: usize, step: isize) {
+        if self.is_locked() {
+            return;
+        }
+
+        let Some(position) = self.messages.get(index).and_then(|m| m.position) else {
+            return;
+        };
+        let siblings = self.tree.siblings(position);
+        let target = siblings
+            .iter()
+            .position(|p| *p == position)
+            .and_then(|number| number.checked_add_signed(step))
+            .and_then(|number| siblings.get(number));
+
+        if let Some(target) = target {
+            self.messages = self.tree.branch(self.tree.last_leaf(*target));
+            self.context = None;
+        }
     }
 
+    // Index in `messages` of the saved message at `position`, its branch is shown if needed
+    pub fn show_position(&mut self, position: usize) -> Option<usize> {
+        if !self.messages.iter().any(|m| m.position == Some(position)) && position < self.tree.len() {
+            self.messages = self.tree.branch(self.tree.last_leaf(position));
+        }
+        self.messages.iter().position(|m| m.position == Some(position))
+    }
+
+    // Answer again the message answered at `index`, or the last answer, with each model given or
+    // configured in `config/compare.json`. The alternatives are shown until one is picked
+    pub fn compare_answer(&mut self, index: Option<usize>, models: &[String]) {
+        if self.is_locked() {
+            return;
+        }
+
+        let is_answer = |m: &Message| matches!(m.role, MessageType::ASSISTANT) && m.position.is_some();
+        let index = match index {
+            Some(index) => Some(index).filter(|i| self.messages.get(*i).is_some_and(is_answer)),
+            None => self.messages.iter().rposition(is_answer),
+        };
+        let Some(index) = index else {
+            self.notify("Select an answer of Néo AI to compare it".to_string());
+            return;
+        };
+
+        let config = CompareConfig::load().and_then(|config| Ok((config.models(models)?, config.config)));
+        let (models, config) = match config {
+            Ok(config) => config,
+            Err(e) => {
+                self.show_error(e);
+                return;
+            }
+        };
+
+        self.comparison = Some(Comparison::new(index, &self.messages[index], &models));
+        let history: Vec<Message> = self.messages[..index]
+            .iter()
+            .filter(|m| !matches!(m.role, MessageType::SYSTEM))
+            .cloned()
+            .collect();
+
+        let (events_tx, events_rx) = unbounded_channel();
+        self.events_rx = Some(events_rx);
+
+        // The alternatives are generated one after the other, local servers run one at a time
+        let task = self.runtime.spawn(async move {
+            for (k, model) in models.into_iter().enumerate() {
+                let k = k + 1; // The answer compared is the first alternative
+                let (tx, mut rx) = unbounded_channel::<StreamUpdate>();
+
+                let generation = compare::generate(&config, &model, history.clone(), tx);
+                let stream = async {
+                    while let Some(update) = rx.recv().await {
+                        if let StreamUpdate::Token(token) = update {
+                            let _ = events_tx.send(AppEvent::Alternative(k, token));
+                        }
+                    }
+                };
+
+                let (result, _) = tokio::join!(generation, stream);
+                let _ = events_tx.send(AppEvent::AlternativeDone(k, Box::new(result)));
+            }
+        });
+        self.task = Some(task);
+    }
+
+    // Keep the alternative `k` as the answer, the others are dropped. The answer compared stays
+    // saved as another version
+    pub fn pick_alternative(&mut self, k: usize) {
+        let Some(comparison) = self.comparison.take() else {
+            return;
+        };
+        if comparison.is_running() {
+            self.comparison = Some(comparison);
+            return;
+        }
+
+        // The messages may have changed since, the alternative must replace the answer compared
+        let index = comparison.index;
+        let compared = comparison.alternatives.first().and_then(|current| current.message.position);
+        if compared.is_none() || self.messages.get(index).and_then(|m| m.position) != compared {
+            return;
+        }
+        if let Some(alternative) = comparison.alternatives.into_iter().nth(k).filter(|_| k > 0) {
+            self.messages.truncate(index);
+            self.append_message(alternative.message);
+        }
+    }
+
+    // Stop comparing without changing the answer
+    pub fn close_comparison(&mut self) {
+        self.cancel();
+        self.comparison = None;
+    }
+
+    // Resume the conversation and save it to its resume file. The last resume is updated with
+    // the messages which followed it, it's only saved again if nothing was said since
     pub fn resume_conv(&mut self) {
-        self.ask("resume"); 
+        if self.is_locked() {
+            return;
+        }
+
+        let last = self.messages.iter().rev().find(|m| !matches!(m.role, MessageType::SYSTEM));
+        match last {
+            None => self.notify("Nothing to resume yet".to_string()),
+            Some(last) if resume::is_resume(last) => {
+                let resume = last.content.clone();
+                self.save_resume(&resume);
+            }
+            Some(_) => self.spawn_pipeline(Some(resume::RESUME_ROUTE)),
+        }
+    }
+
+    fn save_resume(&mut self, resume: &str) {
+        let title = conversation::display_title(&self.conv_id, &self.messages);
+        match resume::save_resume(&self.conv_id, &title, resume) {
+            Ok(path) => self.notify(format!("Resume saved to {}", path.display())),
+            Err(e) => self.show_error(e),
+        }
+    }
+}
+
+// Write a file which doesn't exist yet, with its directories
+fn write_new_file(path: &str, contents: &str) -> Result<()> {
+    let path = std::path::Path::new(path);
+    if path.exists() {
+        return Err(Error::Storage(format!("{} already exists", path.display())));
+    }
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir).map_err(|e| Error::Storage(format!("{}: {}", dir.display(), e)))?;
+    }
+    std::fs::write(path, contents).map_err(|e| Error::Storage(format!("{}: {}", path.display(), e)))
+}
+
+// Ask the LLM to categorise the request between (chat, code, wikipedia)
+async fn categorize(categorize_llm: &LLM, messages: &[Message]) -> Result<String> {
+    let msg = categorize_llm.ask_tools(messages).await?;
+    msg[0]["function"]["arguments"]["category_choice"]
+        .as_str()
+        .map(|categorie| categorie.to_string())
+        .ok_or(Error::Parse("no category_choice in the categorize_message tool call".to_string()))
+}
+
+// Run the module matching `mode`, every token of the answer is sent to `tokens`
+async fn ask(mode: &str, messages: Vec<Message>, project: Option<Arc<Project>>, tokens: TokenSender) -> Result<Answer> {
+    warn(format!("Categorie: {}", mode));
+
+    if mode == resume::RESUME_ROUTE {
+        resume::resume_conv(messages, &tokens).await
+    } else if mode == "code" {
+        code::ask_code(messages, project, &tokens).await
+    } else if mode == "wikipedia" {
+        wikipedia::ask_wiki(&messages, &tokens).await
+    } else {
+        chat::ask_chat(messages, &tokens).await
     }
 }
@@ -1,92 +1,318 @@
+use crate::app::backend::decoder::StreamDecoder;
+use crate::app::backend::init::{server_error, Provider, StreamEvent};
+use crate::app::context::{ContextConfig, ContextUsage, Strategy};
+use crate::app::storage::init as storage;
+use crate::error::{Error, Result};
 use crate::helper::init::warn;
-use reqwest::{header::CONTENT_TYPE, Client};
+use reqwest::{header::CONTENT_TYPE, Client, RequestBuilder, Response};
 use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Local};
 use serde_json::Value;
+use std::collections::HashMap;
 use std::fmt;
-use std::fs::{self, OpenOptions, create_dir_all};
-use std::io::Write;
+use std::fs;
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+use tokio::sync::mpsc::UnboundedSender;
+use uuid::Uuid;
 
+// Running summaries made by the `rolling` context strategy, by hash of the messages they cover.
+// They are kept while the app runs, the messages stay complete in the conversation files
+static ROLLING_SUMMARIES: OnceLock<Mutex<HashMap<u64, String>>> = OnceLock::new();
+
+fn rolling_summaries() -> &'static Mutex<HashMap<u64, String>> {
+    ROLLING_SUMMARIES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+// FNV-1a, the hash of a history is the hash of its last message chained to the previous ones
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+fn hash_message(hash: u64, message: &Message) -> u64 {
+    format!("{}\0{}\0", message.role, message.content)
+        .bytes()
+        .fold(hash, |hash, byte| (hash ^ byte as u64).wrapping_mul(FNV_PRIME))
+}
+
+// What the LLM reports to the UI while the answer is generated
+pub enum StreamUpdate {
+    Token(String),         // New token of the answer
+    Context(ContextUsage), // Size of the prompt sent
+    Trace(String),         // Step of the pipeline worth showing, it isn't part of the answer
+    Restart,               // The answer is generated again, the tokens received are dropped
+}
+
+// Channel used to forward streamed tokens to the UI while the answer is generated
+pub type TokenSender = UnboundedSender<StreamUpdate>;
+
+// Generation options of a model, unset options keep the server defaults. Options not supported
+// by a provider are ignored
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+pub struct Options {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_ctx: Option<u64>, // Context window size in tokens
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>, // Stop sequences
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub num_predict: Option<i64>, // Maximum number of tokens generated
+    #[serde(skip_serializing, default)]
+    pub keep_alive: Option<Value>, // How long the model stays loaded, e.g. "5m" or 300
+}
+
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Deserialize, Debug)]
 pub struct LLM {
     url: String,
-    model: String,
+    pub model: String,
+    #[serde(default)]
+    provider: Provider, // Server API used, Ollama by default
+    #[serde(default)]
+    api_key: Option<String>, // Bearer token for OpenAI compatible servers
     pub system_prompt: String,
     pub tools: serde_json::Value,
+    #[serde(default)]
+    pub options: Options,
+    #[serde(default)]
+    pub context: ContextConfig, // How the history is fitted in the context window
 }
 
 impl LLM {
-    pub fn new(config_file: &str) -> LLM {
-        let contents = fs::read_to_string(config_file).unwrap();
-        serde_json::from_str(&contents).unwrap()
+    pub fn new(config_file: &str) -> Result<LLM> {
+        let contents = fs::read_to_string(config_file)
+            .map_err(|e| Error::Config(format!("{}: {}", config_file, e)))?;
+        serde_json::from_str(&contents).map_err(|e| Error::Config(format!("{}: {}", config_file, e)))
     }
 
-    pub async fn ask(&self, messages: &Vec<Message>) -> Result<String, Box<dyn std::error::Error>> {
-        let client = Client::new();
-        let response = client
+    fn request(&self, body: &Value) -> RequestBuilder {
+        let request = Client::new()
             .post(&self.url)
             .header(CONTENT_TYPE, "application/json")
-            .json(&serde_json::json!({
-                "model": self.model,
-                "messages": messages,
-                "stream": true}))
-            .send()
-            .await?;
+            .json(body);
+
+        match &self.api_key {
+            Some(key) => request.bearer_auth(key),
+            None => request,
+        }
+    }
+
+    // Turn an error status in an error, using the error sent by the server when there's one
+    async fn check_status(response: Response) -> Result<Response> {
+        if let Err(e) = response.error_for_status_ref() {
+            let body = response.json::<Value>().await.unwrap_or(Value::Null);
+            return Err(server_error(&body).unwrap_or(Error::from(e)));
+        }
+        Ok(response)
+    }
+
+    // Reduce the history to fit in the context window, following the configured strategy
+    async fn fit_context(&self, messages: &[Message]) -> (Vec<Message>, ContextUsage) {
+        let budget = self.context.budget(self.options.num_ctx);
+        let (mut kept, dropped) = self.context.fit(messages, budget);
+
+        let summary = match self.context.strategy {
+            _ if dropped.is_empty() => None,
+            Strategy::Summarize => Some(self.summarize(&dropped).await),
+            Strategy::Rolling => Some(self.rolling_summary(&dropped).await),
+            _ => None,
+        };
+
+        if let Some(summary) = summary {
+            match summary {
+                Ok(summary) => {
+                    // The summary takes the place of the dropped messages, after the system ones
+                    let position = kept
+                        .iter()
+                        .position(|m| !matches!(m.role, MessageType::SYSTEM))
+                        .unwrap_or(kept.len());
+                    let summary = Message::new(
+                        MessageType::SYSTEM,
+                        format!("Summary of the earlier conversation:\n{}", summary),
+                    );
+                    kept.insert(position, summary);
+                    (kept, _) = self.context.fit(&kept, budget);
+                }
+                Err(e) => warn(format!("Unable to summarize the history: {}", e)),
+            }
+        }
+
+        let usage = ContextUsage { used: self.context.estimate(&kept), budget };
+        (kept, usage)
+    }
+
+    // Summarize messages with the summarizer LLM, its own context strategy only drops messages
+    async fn summarize(&self, messages: &[Message]) -> Result<String> {
+        let summarizer = LLM::new(&self.context.summarizer)?;
+
+        let mut prompt = messages.to_vec();
+        prompt.push(Message::new(MessageType::USER, summarizer.system_prompt.clone()));
+        let budget = summarizer.context.budget(summarizer.options.num_ctx);
+        let (prompt, _) = summarizer.context.fit(&prompt, budget);
+
+        let (summary, _) = summarizer.stream_answer(&prompt, None).await?;
+        Ok(summary)
+    }
+
+    // Summary of `dropped` updated from the summary of its longest start already made, so only
+    // the messages dropped since are read. Messages are read in chunks fitting in the context of
+    // the summarizer, a long history is summarized a part after the other
+    async fn rolling_summary(&self, dropped: &[Message]) -> Result<String> {
+        // Hash of each start of the history, a summary is found back by the messages it covers
+        let hashes: Vec<u64> = dropped
+            .iter()
+            .scan(FNV_OFFSET, |hash, message| {
+                *hash = hash_message(*hash, message);
+                Some(*hash)
+            })
+            .collect();
+
+        let (mut covered, mut summary) = {
+            let summaries = rolling_summaries().lock().unwrap_or_else(|e| e.into_inner());
+            (1..=hashes.len())
+                .rev()
+                .find_map(|len| summaries.get(&hashes[len - 1]).map(|summary| (len, Some(summary.clone()))))
+                .unwrap_or((0, None))
+        };
+
+        let summarizer = LLM::new(&self.context.summarizer)?;
+        let budget = summarizer.context.budget(summarizer.options.num_ctx);
+        let instructions = Message::new(MessageType::USER, summarizer.system_prompt.clone());
+
+        while covered < dropped.len() {
+            let mut prompt: Vec<Message> = summary
+                .iter()
+                .map(|summary| Message::new(MessageType::SYSTEM, format!("Summary of the earlier conversation:\n{}", summary)))
+                .collect();
+
+            // At least one message is read, `fit` cuts it if it's too long
+            let mut used = summarizer.context.estimate(&prompt) + summarizer.context.estimate(std::slice::from_ref(&instructions));
+            let mut end = covered;
+            while end < dropped.len() {
+                used += summarizer.context.estimate(&dropped[end..end + 1]);
+                if used > budget && end > covered {
+                    break;
+                }
+                end += 1;
+            }
+
+            prompt.extend_from_slice(&dropped[covered..end]);
+            prompt.push(instructions.clone());
+            let (prompt, _) = summarizer.context.fit(&prompt, budget);
+
+            let (text, _) = summarizer.stream_answer(&prompt, None).await?;
+            rolling_summaries()
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .insert(hashes[end - 1], text.clone());
+            summary = Some(text);
+            covered = end;
+        }
+
+        Ok(summary.unwrap_or_default())
+    }
+
+    // Ask the LLM, every streamed token is forwarded to `tokens` if a sender is given
+    pub async fn ask(&self, messages: &[Message], tokens: Option<&TokenSender>) -> Result<Answer> {
+        let start = Instant::now();
+        let (messages, usage) = self.fit_context(messages).await;
+        if let Some(tx) = tokens {
+            let _ = tx.send(StreamUpdate::Context(usage));
+        }
+
+        let (content, metrics) = self.stream_answer(&messages, tokens).await?;
+        Ok(Answer {
+            content,
+            meta: MessageMeta {
+                model: Some(self.model.clone()),
+                endpoint: Some(self.url.clone()),
+                duration_ms: Some(start.elapsed().as_millis() as u64),
+                metrics,
+                ..Default::default()
+            },
+        })
+    }
+
+    // Stream the answer of the LLM, returns it with the usage reported by the server
+    async fn stream_answer(&self, messages: &[Message], tokens: Option<&TokenSender>) -> Result<(String, Option<Metrics>)> {
+        let backend = self.provider.backend();
+        let body = backend.chat_body(&self.model, messages, &self.options, true, None);
+        let response = self.request(&body).send().await?;
 
         let mut full_message = String::new();
+        let mut metrics = None;
 
         // Reading the stream and saving the response
-        match response.error_for_status() {
-            Ok(mut res) => {
-                while let Some(chunk) = res.chunk().await? {
-                    let answer: Value = serde_json::from_slice(chunk.as_ref())?;
+        let mut res = Self::check_status(response).await?;
+        let mut decoder = StreamDecoder::new(backend.framing());
+        let mut done = false;
+
+        while !done {
+            let messages = match res.chunk().await? {
+                Some(chunk) => decoder.push(chunk.as_ref()),
+                None => {
+                    done = true;
+                    decoder.finish().into_iter().collect()
+                }
+            };
 
-                    //warn(answer.to_string());
-                    if answer["done"].as_bool().unwrap_or(false) {
+            for message in messages {
+                //warn(message.clone());
+                let msg = match backend.parse_stream_message(&message)? {
+                    StreamEvent::Token(msg) => msg,
+                    StreamEvent::Metrics(usage) => {
+                        metrics = Some(usage);
+                        continue;
+                    }
+                    StreamEvent::Done(usage) => {
+                        metrics = usage.or(metrics);
+                        done = true;
                         break;
                     }
+                    StreamEvent::Skip => continue,
+                };
 
-                    let msg = answer["message"]["content"].as_str().unwrap_or("\n");
-
-                    full_message.push_str(msg);
+                if let Some(tx) = tokens {
+                    // The receiver may be gone if the UI stopped listening, the answer is
+                    // still returned as a whole
+                    let _ = tx.send(StreamUpdate::Token(msg.clone()));
                 }
+
+                full_message.push_str(&msg);
             }
-            Err(e) => return Err(Box::new(e)),
         }
     
         warn(full_message.clone());
-        Ok(full_message)
+        Ok((full_message, metrics))
     }
 
-    // Use tools functionnality of Ollama, only some models supports it:
-    // https://ollama.com/search?c=tools
-    pub async fn ask_tools(&self, messages: &Vec<Message>) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
-        let client = Client::new();
-        let response = client
-            .post(&self.url)
-            .header(CONTENT_TYPE, "application/json")
-            .json(&serde_json::json!({
-                "model": self.model,
-                "messages": messages,
-                "stream": false,
-                "tools": self.tools}))
-            .send()
-            .await?.json::<Value>().await?;
+    // Ask the LLM to answer with tool calls, only some models supports it
+    pub async fn ask_tools(&self, messages: &[Message]) -> Result<serde_json::Value> {
+        let (messages, _) = self.fit_context(messages).await;
+        let backend = self.provider.backend();
+        let body = backend.chat_body(&self.model, &messages, &self.options, false, Some(&self.tools));
+        let response = self.request(&body).send().await?;
+        let response = Self::check_status(response).await?;
+        let response = response.json::<Value>().await?;
 
         //warn(response.to_string());
 
-        if let Some(tool_calls) = response
-            .get("message")
-                .and_then(|msg| msg.get("tool_calls"))
-                .cloned()
-        {
-            Ok(tool_calls)
-        } else {
-            Err("tool_calls not found".into())
+        if let Some(e) = server_error(&response) {
+            return Err(e);
         }
+
+        backend
+            .tool_calls(&response)
+            .ok_or(Error::Parse("tool_calls not found".to_string()))
     }
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub enum MessageType {
     ASSISTANT,
     SYSTEM,
@@ -103,48 +329,83 @@ impl fmt::Display for MessageType {
     }
 }
 
-#[derive(Debug, Serialize, Clone)]
+// Usage reported by the LLM server at the end of an answer
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Metrics {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_tokens: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completion_tokens: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_duration_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub load_duration_ms: Option<u64>, // Time spent loading the model
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_duration_ms: Option<u64>, // Time spent reading the prompt
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub eval_duration_ms: Option<u64>, // Time spent generating the answer
+}
+
+// Information saved with a message in the conversation file, it's never sent to the LLM
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct MessageMeta {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<DateTime<Local>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub route: Option<String>, // Module which answered (chat, wikipedia, resume...)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>, // Time between the request and the complete answer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<Metrics>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<String>, // Documents used to answer, e.g. Wikipedia articles
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<usize>, // Position of the previous message of the branch, when it isn't the one saved before
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edit_of: Option<usize>, // Position of the user message replaced by this one
+}
+
+// Answer of a LLM or a module, with what is known about how it was made
+pub struct Answer {
+    pub content: String,
+    pub meta: MessageMeta,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Message {
     pub role: MessageType,
     pub content: String,
+    #[serde(flatten)]
+    pub meta: MessageMeta, // Saved alongside role and content, older files don't have it
+    #[serde(skip)]
+    pub position: Option<usize>, // Position in the saved conversation, None if not saved
 }
 
 impl Message {
     pub fn new(role: MessageType, content: String) -> Message {
-        Message { role, content }
+        Message { role, content, meta: MessageMeta::default(), position: None }
     }
 
-    pub fn default() -> Message {
-        Message {
-            role: MessageType::USER,
-            content: "".to_string(),
-        }
+    pub fn from_answer(answer: Answer) -> Message {
+        Message { role: MessageType::ASSISTANT, content: answer.content, meta: answer.meta, position: None }
     }
 
-    pub fn save_message(&self, conv_id: String) -> Result<(), Box<dyn std::error::Error>> {
-        // Create conv directory if doesn't exist
-        create_dir_all("conv")?;
-
-        // Save message
-        let mut file = OpenOptions::new()
-            .write(true)
-            .append(true)
-            .create(true)
-            .open("conv/".to_string() + &conv_id)
-            .unwrap();
-
-        writeln!(file, "{}", serde_json::to_string(self)?)?;
-
-        Ok(())
+    // Append the message to the conversation in the configured storage
+    pub fn save_message(&self, conv_id: &Uuid) -> Result<()> {
+        storage::open()?.append(conv_id, self)
     }
 }
 
 impl fmt::Display for Message {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self.role {
-            MessageType::USER => return write!(f, "You: {}", self.content),
-            MessageType::SYSTEM => return write!(f, "System: {}", self.content),
-            MessageType::ASSISTANT => return write!(f, "Néo AI: {}", self.content),
+            MessageType::USER => write!(f, "You: {}", self.content),
+            MessageType::SYSTEM => write!(f, "System: {}", self.content),
+            MessageType::ASSISTANT => write!(f, "Néo AI: {}", self.content),
         }
     }
 }
@@ -1,4 +1,5 @@
 pub mod chat;
 pub mod code;
 pub mod resume;
+pub mod title;
 pub mod wikipedia;
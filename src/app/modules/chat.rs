@@ -1,9 +1,16 @@
-use crate::app::llm::{LLM, Message, MessageType};
+use crate::app::llm::{Answer, LLM, Message, MessageType, TokenSender};
+use crate::error::Result;
 
-pub async fn ask_chat(mut messages: Vec<Message>) -> Result<String, Box<dyn std::error::Error>> {
-    let chat_llm = LLM::new("config/chat-LLM.json");
+pub const CHAT_CONFIG: &str = "config/chat-LLM.json";
+
+pub async fn ask_chat(messages: Vec<Message>, tokens: &TokenSender) -> Result<Answer> {
+    ask_with(LLM::new(CHAT_CONFIG)?, messages, tokens).await
+}
+
+// Answer with the persona of `chat_llm`, used to compare answers of several models
+pub async fn ask_with(chat_llm: LLM, mut messages: Vec<Message>, tokens: &TokenSender) -> Result<Answer> {
     messages.push(Message::new(MessageType::USER, chat_llm.system_prompt.to_string()));
   
-    let result: String = chat_llm.ask(&messages).await?;
+    let result: Answer = chat_llm.ask(&messages, Some(tokens)).await?;
     Ok(result)
 }
@@ -0,0 +1,267 @@
+use crate::app::llm::{Answer, LLM, Message, MessageType, StreamUpdate, TokenSender};
+use crate::app::project::{self, Project};
+use crate::app::sandbox::{self, SandboxConfig};
+use crate::error::{Error, Result};
+use crate::helper::init::warn;
+use regex::Regex;
+use std::sync::Arc;
+
+pub const CODE_CONFIG: &str = "config/code-LLM.json";
+
+// Fenced code block of a message
+#[derive(Debug, Clone, PartialEq)]
+pub struct CodeBlock {
+    pub language: Option<String>, // Written after the opening fence, lowercase
+    pub code: String,
+}
+
+// Answer with the coder model, its system prompt comes first so the whole history is read as a
+// programming discussion. Rust code which doesn't compile is given back to the model with the
+// diagnostics of cargo, `repairs` times at most (see config/sandbox.json). The parts of the
+// `project` attached to the conversation relevant to the question are added before it
+pub async fn ask_code(mut messages: Vec<Message>, project: Option<Arc<Project>>, tokens: &TokenSender) -> Result<Answer> {
+    let code_llm = LLM::new(CODE_CONFIG)?;
+    let repairs = SandboxConfig::load()?.repairs;
+    messages.insert(0, Message::new(MessageType::SYSTEM, code_llm.system_prompt.to_string()));
+
+    let mut sources = Vec::new();
+    if let Some(project) = project {
+        let query = messages.last().map(|m| m.content.clone()).unwrap_or_default();
+        let context = tokio::task::spawn_blocking(move || project::context(&project, &query))
+            .await
+            .map_err(|e| Error::Retrieval(e.to_string()))??;
+        if let Some((context, citations)) = context {
+            let _ = tokens.send(StreamUpdate::Trace(format!("Project files: {}", citations.join(", "))));
+            messages.insert(messages.len() - 1, context);
+            sources = citations;
+        }
+    }
+
+    let mut result = answer(&code_llm, &messages, tokens).await?;
+    let mut attempt = 1;
+    loop {
+        let diagnostics = match repairs {
+            0 => None,
+            _ => check_rust(&result.content, tokens).await,
+        };
+        let Some(diagnostics) = diagnostics else {
+            if attempt > 1 {
+                let _ = tokens.send(StreamUpdate::Trace(format!("Attempt {} compiles", attempt)));
+            }
+            break;
+        };
+        if attempt > repairs {
+            let _ = tokens.send(StreamUpdate::Trace(format!(
+                "Attempt {} doesn't compile either, the answer is kept after {} repairs:\n{}",
+                attempt, repairs, diagnostics
+            )));
+            break;
+        }
+        let _ = tokens.send(StreamUpdate::Trace(format!(
+            "Attempt {} doesn't compile, asking for a fix:\n{}",
+            attempt, diagnostics
+        )));
+        let _ = tokens.send(StreamUpdate::Restart);
+
+        messages.push(Message::new(MessageType::ASSISTANT, result.content.clone()));
+        messages.push(Message::new(
+            MessageType::USER,
+            format!(
+                "This code doesn't compile, cargo check says:\n```\n{}\n```\nFix it and give the whole answer again.",
+                diagnostics
+            ),
+        ));
+        result = answer(&code_llm, &messages, tokens).await?;
+        attempt += 1;
+    }
+    result.meta.sources = sources;
+    Ok(result)
+}
+
+async fn answer(code_llm: &LLM, messages: &[Message], tokens: &TokenSender) -> Result<Answer> {
+    let mut result: Answer = code_llm.ask(messages, Some(tokens)).await?;
+    result.content = tidy_code(&result.content);
+    Ok(result)
+}
+
+// Diagnostics of the Rust blocks of an answer which don't compile, None if they all do. The
+// answer is kept as it is when the check can't be run. Blocks using other crates than the
+// standard library can't be checked offline, they are skipped
+async fn check_rust(content: &str, tokens: &TokenSender) -> Option<String> {
+    let mut blocks: Vec<(usize, CodeBlock)> = Vec::new();
+    for (i, block) in code_blocks(content).into_iter().enumerate() {
+        if !matches!(block.language.as_deref(), Some("rust" | "rs")) {
+            continue;
+        }
+        let crates = external_crates(&block.code);
+        if crates.is_empty() {
+            blocks.push((i, block));
+        } else {
+            let _ = tokens.send(StreamUpdate::Trace(format!(
+                "Block {} uses {}, it isn't checked",
+                i + 1,
+                crates.join(", ")
+            )));
+        }
+    }
+
+    let checked = tokio::task::spawn_blocking(move || {
+        let mut diagnostics = Vec::new();
+        for (i, block) in blocks {
+            if let Some(errors) = sandbox::cargo_check(&block.code)? {
+                diagnostics.push(format!("Block {}:\n{}", i + 1, errors));
+            }
+        }
+        Ok::<_, Error>(diagnostics)
+    })
+    .await
+    .map_err(|e| Error::Sandbox(e.to_string()))
+    .and_then(|result| result);
+
+    match checked {
+        Ok(diagnostics) if diagnostics.is_empty() => None,
+        Ok(diagnostics) => Some(diagnostics.join("\n\n")),
+        Err(e) => {
+            warn(format!("Rust check: {}", e));
+            None
+        }
+    }
+}
+
+// Crates used by Rust code which aren't part of the standard library, from its `use`, `extern
+// crate` and attributes like `#[tokio::main]`. Modules declared in the code aren't crates
+fn external_crates(code: &str) -> Vec<String> {
+    let paths = Regex::new(r"(?m)^\s*(?:pub(?:\([^)]*\))?\s+)?(?:use\s+(?:::)?|extern\s+crate\s+)([A-Za-z_]\w*)|#\[\s*([A-Za-z_]\w*)::").unwrap();
+    let modules = Regex::new(r"(?m)^\s*(?:pub(?:\([^)]*\))?\s+)?mod\s+([A-Za-z_]\w*)").unwrap();
+    let local: Vec<&str> = modules.captures_iter(code).filter_map(|c| c.get(1)).map(|m| m.as_str()).collect();
+
+    let mut crates: Vec<String> = paths
+        .captures_iter(code)
+        .filter_map(|captures| captures.get(1).or(captures.get(2)))
+        .map(|name| name.as_str())
+        .filter(|name| !["std", "core", "alloc", "crate", "self", "super"].contains(name) && !local.contains(name))
+        .map(|name| name.to_string())
+        .collect();
+    crates.sort();
+    crates.dedup();
+    crates
+}
+
+// Code blocks of a message, in order. A block left open ends with the message
+pub fn code_blocks(content: &str) -> Vec<CodeBlock> {
+    let mut blocks = Vec::new();
+    let mut current: Option<CodeBlock> = None;
+
+    for line in content.lines() {
+        let fence = line.trim_start().strip_prefix("```");
+        match (fence, current.take()) {
+            (Some(_), Some(block)) => blocks.push(block),
+            (Some(info), None) => {
+                let language = info.split_whitespace().next().map(|language| language.to_lowercase());
+                current = Some(CodeBlock { language, code: String::new() });
+            }
+            (None, Some(mut block)) => {
+                block.code.push_str(line);
+                block.code.push('\n');
+                current = Some(block);
+            }
+            (None, None) => {}
+        }
+    }
+    blocks.extend(current);
+    blocks
+}
+
+// Blocks without a language get the one their code looks like, and a block left open because
+// the answer was cut is closed, so the code can be read and extracted
+fn tidy_code(content: &str) -> String {
+    let mut tidy = String::new();
+    let mut lines = content.lines();
+    let mut in_code = false;
+
+    while let Some(line) = lines.next() {
+        let fence = line.trim_start().strip_prefix("```");
+        match fence {
+            Some(info) if !in_code && info.trim().is_empty() => {
+                // The block is read ahead to guess its language
+                let code: Vec<&str> = lines
+                    .clone()
+                    .take_while(|line| !line.trim_start().starts_with("```"))
+                    .collect();
+                match guess_language(&code.join("\n")) {
+                    Some(language) => tidy.push_str(&format!("{}{}", line.trim_end(), language)),
+                    None => tidy.push_str(line),
+                }
+                in_code = true;
+            }
+            Some(_) => {
+                tidy.push_str(line);
+                in_code = !in_code;
+            }
+            None => tidy.push_str(line),
+        }
+        tidy.push('\n');
+    }
+
+    if in_code {
+        tidy.push_str("```\n");
+    }
+    tidy.trim_end().to_string()
+}
+
+// Language of a block from its first lines, only for the languages worth telling apart
+fn guess_language(code: &str) -> Option<&'static str> {
+    let first = code.trim_start();
+    let has = |patterns: &[&str]| patterns.iter().any(|pattern| code.contains(pattern));
+
+    if first.starts_with("#!") {
+        return match first.lines().next() {
+            Some(shebang) if shebang.contains("python") => Some("python"),
+            _ => Some("sh"),
+        };
+    }
+    if has(&["fn ", "let mut ", "use std::", "impl ", "println!"]) {
+        Some("rust")
+    } else if code.lines().any(is_python) {
+        Some("python")
+    } else if code.lines().any(|line| {
+        let line = line.trim_start();
+        ["$ ", "echo ", "cd ", "sudo ", "apt ", "cargo ", "pip ", "export "].iter().any(|command| line.starts_with(command))
+    }) {
+        Some("sh")
+    } else {
+        None
+    }
+}
+
+// Lines only Python is written with, `import` is left out as JavaScript has it too
+fn is_python(line: &str) -> bool {
+    let line = line.trim();
+    (line.starts_with("def ") && line.ends_with(':'))
+        || line.starts_with("elif ")
+        || (line.starts_with("print(") && !line.ends_with(';'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn external_crates_skip_the_standard_library_and_local_modules() {
+        let code = "use std::collections::HashMap;\nuse serde::{Deserialize, Serialize};\nextern crate rand;\n\
+            mod helpers { pub fn f() {} }\nuse helpers::f;\npub(crate) use self::helpers as h;\n\
+            #[tokio::main]\nasync fn main() { let _ = core::mem::size_of::<u8>(); }\n";
+        assert_eq!(external_crates(code), ["rand", "serde", "tokio"]);
+        assert!(external_crates("use std::io;\nfn main() {}\n").is_empty());
+    }
+
+    #[test]
+    fn python_is_only_guessed_from_its_own_syntax() {
+        assert_eq!(guess_language("def add(a, b):\n    return a + b"), Some("python"));
+        assert_eq!(guess_language("if x:\n    pass\nelif y:\n    pass"), Some("python"));
+        assert_eq!(guess_language("print(\"hello\")"), Some("python"));
+        assert_eq!(guess_language("import React from 'react';\nconsole.log(1);"), None);
+        assert_eq!(guess_language("import os"), None);
+        assert_eq!(guess_language("print(x);"), None);
+    }
+}
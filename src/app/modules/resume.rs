@@ -1,9 +1,105 @@
-use crate::app::llm::{LLM, Message, MessageType};
+use crate::app::llm::{Answer, LLM, Message, MessageType, TokenSender};
+use crate::app::storage::init::StorageConfig;
+use crate::error::{Error, Result};
+use chrono::Local;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use uuid::Uuid;
 
-pub async fn resume_conv(mut messages: Vec<Message>) -> Result<String, Box<dyn std::error::Error>> {
-    let resume_llm = LLM::new("config/resume-LLM.json");
-    messages.push(Message::new(MessageType::USER, resume_llm.system_prompt.to_string()));
-  
-    let result: String = resume_llm.ask(&messages).await?;
+pub const RESUME_CONFIG: &str = "config/resume-LLM.json";
+
+// Route of the resumes saved in the conversation
+pub const RESUME_ROUTE: &str = "resume";
+
+// Asked instead of the system prompt when a resume already exists
+const UPDATE_PROMPT: &str = "Update the resume of the earlier conversation with the messages which followed it. \
+Keep its points which are still true and give the whole resume as a Markdown bullet list.";
+
+// Messages made by this module, they aren't part of the discussion itself
+pub fn is_resume(message: &Message) -> bool {
+    message.meta.route.as_deref() == Some(RESUME_ROUTE)
+}
+
+// The last resume of the conversation is updated with the messages which followed it, so the
+// whole conversation is only read the first time
+pub async fn resume_conv(messages: Vec<Message>, tokens: &TokenSender) -> Result<Answer> {
+    let resume_llm = LLM::new(RESUME_CONFIG)?;
+
+    let mut prompt = Vec::new();
+    let (new_messages, instructions) = match messages.iter().rposition(is_resume) {
+        Some(last) => {
+            prompt.push(Message::new(
+                MessageType::SYSTEM,
+                format!("Resume of the earlier conversation:\n{}", messages[last].content),
+            ));
+            (&messages[last + 1..], UPDATE_PROMPT.to_string())
+        }
+        None => (&messages[..], resume_llm.system_prompt.to_string()),
+    };
+    prompt.extend(new_messages.iter().filter(|m| !is_resume(m)).cloned());
+    prompt.push(Message::new(MessageType::USER, instructions));
+
+    let result: Answer = resume_llm.ask(&prompt, Some(tokens)).await?;
     Ok(result)
 }
+
+// Resume file of a conversation, next to the saved conversations
+pub fn resume_path(conv_id: &Uuid) -> Result<PathBuf> {
+    Ok(StorageConfig::load()?.dir().join(format!("{}.resume.md", conv_id)))
+}
+
+// Write the resume file of a conversation, replacing the previous one. Returns its path
+pub fn save_resume(conv_id: &Uuid, title: &str, resume: &str) -> Result<PathBuf> {
+    let path = resume_path(conv_id)?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| Error::Storage(format!("{}: {}", dir.display(), e)))?;
+    }
+
+    let contents = format!(
+        "# {}\n\n*Resume of conversation {}, updated on {}*\n\n{}\n",
+        title,
+        conv_id,
+        Local::now().format("%Y-%m-%d %H:%M"),
+        bullets(resume)
+    );
+    fs::write(&path, contents).map_err(|e| Error::Storage(format!("{}: {}", path.display(), e)))?;
+    Ok(path)
+}
+
+// Content of the resume file, None if no resume was saved
+pub fn load_resume(conv_id: &Uuid) -> Result<Option<String>> {
+    let path = resume_path(conv_id)?;
+    match fs::read_to_string(&path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(Error::Storage(format!("{}: {}", path.display(), e))),
+    }
+}
+
+// Remove the resume file, nothing is done if it doesn't exist
+pub fn delete_resume(conv_id: &Uuid) -> Result<()> {
+    let path = resume_path(conv_id)?;
+    match fs::remove_file(&path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(Error::Storage(format!("{}: {}", path.display(), e))),
+    }
+}
+
+// The models use several bullet styles, they are all written as `- `
+fn bullets(resume: &str) -> String {
+    resume
+        .trim()
+        .lines()
+        .map(|line| {
+            let trimmed = line.trim_start();
+            let indent = &line[..line.len() - trimmed.len()];
+            match ["* ", "• ", "+ "].iter().find_map(|bullet| trimmed.strip_prefix(bullet)) {
+                Some(point) => format!("{}- {}", indent, point),
+                None => line.trim_end().to_string(),
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
@@ -0,0 +1,29 @@
+use crate::app::llm::{LLM, Message, MessageType};
+use crate::error::{Error, Result};
+
+// Longest title kept, small models don't always follow the instructions
+const MAX_TITLE_LENGTH: usize = 60;
+
+// Make a short title for the conversation, the answer isn't streamed
+pub async fn make_title(mut messages: Vec<Message>) -> Result<String> {
+    let title_llm = LLM::new("config/title-LLM.json")?;
+    messages.push(Message::new(MessageType::USER, title_llm.system_prompt.to_string()));
+
+    let answer = title_llm.ask(&messages, None).await?;
+    clean_title(&answer.content).ok_or(Error::Parse("empty title".to_string()))
+}
+
+// Keep the first line without the decorations models like to add (quotes, "Title:", markdown)
+fn clean_title(answer: &str) -> Option<String> {
+    let line = answer.lines().map(|line| line.trim()).find(|line| !line.is_empty())?;
+    let line = line.strip_prefix("Title:").unwrap_or(line);
+    let title = line.trim_matches(|c: char| c.is_whitespace() || "\"'*#`.".contains(c));
+
+    if title.is_empty() {
+        return None;
+    }
+    if title.chars().count() > MAX_TITLE_LENGTH {
+        return Some(format!("{}...", title.chars().take(MAX_TITLE_LENGTH).collect::<String>()));
+    }
+    Some(title.to_string())
+}
@@ -1,26 +1,31 @@
-use crate::app::llm::{Message, MessageType, LLM};
+use crate::app::llm::{Answer, Message, MessageType, TokenSender, LLM};
 use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use crate::error::{Error, Result};
 use crate::helper::init::warn;
 use std::fs;
 use select::document::Document;
 use select::predicate::{Name, Class};
 use regex::Regex;
 
-pub async fn ask_wiki(messages: &Vec<Message>) -> Result<String, Box<dyn std::error::Error>> {
-    let wiki_search = LLM::new("config/wiki/wiki-search.json");
-    let wiki_best = LLM::new("config/wiki/wiki-best.json");
-    let wiki_resume = LLM::new("config/wiki/wiki-resume.json");
+pub async fn ask_wiki(messages: &[Message], tokens: &TokenSender) -> Result<Answer> {
+    let wiki_search = LLM::new("config/wiki/wiki-search.json")?;
+    let wiki_best = LLM::new("config/wiki/wiki-best.json")?;
+    let wiki_resume = LLM::new("config/wiki/wiki-resume.json")?;
 
-    let settings: serde_json::Value = serde_json::from_str(&fs::read_to_string("config/wiki/wiki.json").unwrap()).unwrap();
-    let wiki_url: String = settings.get("wiki_url").unwrap().to_string().replace("\"", "");
-    let zim_name: String = settings.get("zim_name").unwrap().to_string().replace("\"", "");
+    let settings: serde_json::Value = serde_json::from_str(&fs::read_to_string("config/wiki/wiki.json")
+        .map_err(|e| Error::Config(format!("config/wiki/wiki.json: {}", e)))?)
+        .map_err(|e| Error::Config(format!("config/wiki/wiki.json: {}", e)))?;
+    let wiki_url: String = setting(&settings, "wiki_url")?;
+    let zim_name: String = setting(&settings, "zim_name")?;
 
     // Search articles corresponding to user query
-    let user_query: Message = messages.last().unwrap().clone();
+    let user_query: Message = messages.last()
+        .ok_or(Error::Retrieval("no query to search on Wikipedia".to_string()))?
+        .clone();
     let articles: Vec<String> = search_articles(user_query.clone(), wiki_search, &wiki_url, &zim_name).await?;
 
     // Find best article to respond user query
-    let best_article_content = find_get_best_article(articles, &user_query.content, wiki_best, &wiki_url, &zim_name).await?;
+    let (best_article_content, best_article_url) = find_get_best_article(articles, &user_query.content, wiki_best, &wiki_url, &zim_name).await?;
 
     // Resume article and create the response
     let messages = vec![
@@ -28,12 +33,20 @@ pub async fn ask_wiki(messages: &Vec<Message>) -> Result<String, Box<dyn std::er
         Message::new(MessageType::USER, format!("The users query is: {}", user_query.content)),
         Message::new(MessageType::USER, format!("The search results are: {}", best_article_content)),
     ];
-    let query_response: String = wiki_resume.ask(&messages).await.unwrap();
+    let mut query_response: Answer = wiki_resume.ask(&messages, Some(tokens)).await?;
+    query_response.meta.sources.push(best_article_url);
 
     Ok(query_response)
 }
 
-async fn search_articles(user_query: Message, search_llm: LLM, wiki_url: &String, zim_name: &String) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+fn setting(settings: &serde_json::Value, name: &str) -> Result<String> {
+    settings.get(name)
+        .and_then(|value| value.as_str())
+        .map(|value| value.to_string())
+        .ok_or(Error::Config(format!("config/wiki/wiki.json: missing {}", name)))
+}
+
+async fn search_articles(user_query: Message, search_llm: LLM, wiki_url: &String, zim_name: &String) -> Result<Vec<String>> {
     // Use LLM to create 4 queries and fetch articles with those 4 queries
     let messages = vec![
         Message::new(MessageType::SYSTEM, search_llm.system_prompt.clone()),
@@ -41,7 +54,12 @@ async fn search_articles(user_query: Message, search_llm: LLM, wiki_url: &String
     ];
     let result = search_llm.ask_tools(&messages).await?;
 
-    let queries: Vec<String> = result[0]["function"]["arguments"]["queries"].as_array().unwrap().iter().map(|x| x.as_str().unwrap().to_string()).collect();
+    let queries: Vec<String> = result[0]["function"]["arguments"]["queries"].as_array()
+        .ok_or(Error::Parse("no search queries in the search_engine tool call".to_string()))?
+        .iter()
+        .filter_map(|x| x.as_str())
+        .map(|x| x.to_string())
+        .collect();
 
     // Search articles on wikipedia API
     let mut articles: Vec<String> = Vec::new();
@@ -49,7 +67,7 @@ async fn search_articles(user_query: Message, search_llm: LLM, wiki_url: &String
         warn(query.clone());
 
         // Request kiwix API for articles matching query
-        let encoded_query = utf8_percent_encode(&query, NON_ALPHANUMERIC).to_string(); 
+        let encoded_query = utf8_percent_encode(query, NON_ALPHANUMERIC).to_string(); 
         let client = reqwest::Client::new();
         let url = format!("{}/search?books.name={}&pattern={}", wiki_url, zim_name, encoded_query);
         let body = client.get(url).send().await?.text().await?;
@@ -58,7 +76,8 @@ async fn search_articles(user_query: Message, search_llm: LLM, wiki_url: &String
         let document = Document::from(body.as_str());
 
         // Select articles title from the query
-        let results_div = document.find(Class("results")).next().unwrap();
+        let results_div = document.find(Class("results")).next()
+            .ok_or(Error::Retrieval(format!("no search results from kiwix for \"{}\"", query)))?;
         for node in results_div.find(Name("a")) {
             let article = node.text();
             articles.push(article.clone());
@@ -67,7 +86,7 @@ async fn search_articles(user_query: Message, search_llm: LLM, wiki_url: &String
     Ok(articles)
 }
 
-async fn find_get_best_article(articles: Vec<String>, user_query: &String, best_llm: LLM, wiki_url: &String, zim_name: &String) -> Result<String, Box<dyn std::error::Error>> {
+async fn find_get_best_article(articles: Vec<String>, user_query: &String, best_llm: LLM, wiki_url: &String, zim_name: &String) -> Result<(String, String)> {
     // Create a string with all the articles title
     let mut articles_headings: String = String::new();
     for article in articles {
@@ -77,15 +96,15 @@ async fn find_get_best_article(articles: Vec<String>, user_query: &String, best_
     let messages = vec![
         Message::new(MessageType::SYSTEM, best_llm.system_prompt.clone()),
         Message::new(MessageType::USER, format!("The user's query is: {}. Here are the headings:\n{}\n\nPlease select the most relevant heading. Output the heading only and nothing else.", user_query, articles_headings))];
-    let best_article = best_llm.ask(&messages).await?;
+    let best_article = best_llm.ask(&messages, None).await?.content;
 
     // wiki query get article content & parse
     let client = reqwest::Client::new();
     let url: String = format!("{}/content/{}/A/{}", wiki_url, zim_name, best_article.replace("*","").replace(" ", "_"));
-    let body = client.get(url).send().await?.text().await?;
+    let body = client.get(&url).send().await?.text().await?;
     let content = extract_text_from_tags(&body);
 
-    Ok(content)
+    Ok((content, url))
 }
 
 fn extract_text_from_tags(html: &str) -> String {
@@ -93,7 +112,7 @@ fn extract_text_from_tags(html: &str) -> String {
     let re = Regex::new(r#"<p[^>]*>(.*?)</p>|<h1[^>]*>(.*?)</h1>|<h2[^>]*>(.*?)</h2>|<h3[^>]*>(.*?)</h3>"#).unwrap();
 
     // Utiliser l'expression régulière pour capturer le contenu des balises <p>, <h1>, <h2>, <h3>
-    let text = re.captures_iter(html)
+    re.captures_iter(html)
         .flat_map(|cap| {
             // Trouver le premier groupe capturé non vide (parmi cap[1] à cap[4])
             (1..=4)
@@ -103,6 +122,5 @@ fn extract_text_from_tags(html: &str) -> String {
                 .collect::<Vec<_>>()
         })
         .collect::<Vec<_>>() // collect words
-        .join(" "); // join with spaces
-    text
+        .join(" ") // join with spaces
 }
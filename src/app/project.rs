@@ -0,0 +1,205 @@
+use crate::app::index::InvertedIndex;
+use crate::app::llm::{Message, MessageType};
+use crate::app::storage::init::StorageConfig;
+use crate::error::{Error, Result};
+use crate::helper::config::load_config;
+use ignore::WalkBuilder;
+use serde::Deserialize;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use uuid::Uuid;
+
+// How the files of a project are cut and chosen
+pub const PROJECT_CONFIG: &str = "config/project.json";
+
+// Content of `config/project.json`
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct ProjectConfig {
+    pub chunk_lines: usize, // Lines of a chunk, files are cut in chunks
+    pub max_chunks: usize,  // Chunks added to the prompt
+    pub max_chars: usize,   // Size of all the chunks added to the prompt
+    pub max_file_kb: u64,   // Bigger files aren't indexed
+    pub max_files: usize,   // Files indexed, the others are ignored
+}
+
+impl Default for ProjectConfig {
+    fn default() -> Self {
+        Self { chunk_lines: 40, max_chunks: 6, max_chars: 12000, max_file_kb: 256, max_files: 5000 }
+    }
+}
+
+impl ProjectConfig {
+    pub fn load() -> Result<ProjectConfig> {
+        load_config(PROJECT_CONFIG)
+    }
+}
+
+// Lines of a file of the project
+pub struct Chunk {
+    pub path: String, // Relative to the project directory
+    pub start: usize, // First line, from 1
+    pub end: usize,   // Last line, included
+    pub text: String,
+}
+
+impl Chunk {
+    // How the chunk is cited in the answer and its sources
+    pub fn citation(&self) -> String {
+        format!("{}:{}-{}", self.path, self.start, self.end)
+    }
+}
+
+// Inverted index of the text files of a project, `.gitignore` and hidden files are skipped
+pub struct ProjectIndex {
+    chunks: InvertedIndex<Chunk>,
+}
+
+impl ProjectIndex {
+    pub fn build(root: &Path, config: &ProjectConfig) -> Result<ProjectIndex> {
+        let mut index = ProjectIndex { chunks: InvertedIndex::new() };
+
+        // `.gitignore` is read even when the project isn't a git repository
+        let files = WalkBuilder::new(root)
+            .require_git(false)
+            .build()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_some_and(|kind| kind.is_file()))
+            .filter(|entry| entry.metadata().is_ok_and(|metadata| metadata.len() <= config.max_file_kb * 1024))
+            .take(config.max_files);
+
+        for entry in files {
+            // Binary files aren't text and can't help
+            let Ok(contents) = fs::read_to_string(entry.path()) else {
+                continue;
+            };
+            if contents.contains('\0') {
+                continue;
+            }
+
+            let path = entry.path().strip_prefix(root).unwrap_or(entry.path()).to_string_lossy().to_string();
+            let lines: Vec<&str> = contents.lines().collect();
+            for (i, lines) in lines.chunks(config.chunk_lines.max(1)).enumerate() {
+                let start = i * config.chunk_lines.max(1) + 1;
+                let chunk = Chunk {
+                    path: path.clone(),
+                    start,
+                    end: start + lines.len() - 1,
+                    text: lines.join("\n"),
+                };
+                // The path is indexed with the text, asking about a file finds it
+                let text = format!("{} {}", chunk.path, chunk.text);
+                index.chunks.add(chunk, &text);
+            }
+        }
+        Ok(index)
+    }
+
+    // Chunks the most relevant to the query within the limits of the configuration, in the
+    // order of the files
+    pub fn retrieve(&self, query: &str, config: &ProjectConfig) -> Vec<&Chunk> {
+        let mut chosen: Vec<&Chunk> = Vec::new();
+        let mut size = 0;
+        for (chunk, _) in self.chunks.search(query) {
+            if chosen.len() == config.max_chunks {
+                break;
+            }
+            if size + chunk.text.len() > config.max_chars {
+                continue;
+            }
+            size += chunk.text.len();
+            chosen.push(chunk);
+        }
+
+        chosen.sort_by(|a, b| (&a.path, a.start).cmp(&(&b.path, b.start)));
+        chosen
+    }
+}
+
+// Project attached to a conversation. Its index is built for the first code question and kept
+// until the project is attached again
+pub struct Project {
+    pub root: PathBuf,
+    index: OnceLock<ProjectIndex>,
+}
+
+impl Project {
+    pub fn new(root: PathBuf) -> Project {
+        Project { root, index: OnceLock::new() }
+    }
+
+    // Blocks the thread while the files are read the first time
+    fn index(&self, config: &ProjectConfig) -> Result<&ProjectIndex> {
+        if let Some(index) = self.index.get() {
+            return Ok(index);
+        }
+        let index = ProjectIndex::build(&self.root, config)?;
+        Ok(self.index.get_or_init(|| index))
+    }
+}
+
+// Prompt message with the parts of the project relevant to `query`, and their citations. None
+// if nothing in the project matches
+pub fn context(project: &Project, query: &str) -> Result<Option<(Message, Vec<String>)>> {
+    let config = ProjectConfig::load()?;
+    let chunks = project.index(&config)?.retrieve(query, &config);
+    if chunks.is_empty() {
+        return Ok(None);
+    }
+
+    let name = project.root.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    let mut content = format!(
+        "Parts of the files of the project {} which may help to answer. When you use one, cite its path and lines like `src/main.rs:1-40`.\n",
+        name
+    );
+    for chunk in &chunks {
+        let language = Path::new(&chunk.path).extension().map(|e| e.to_string_lossy().to_string()).unwrap_or_default();
+        content.push_str(&format!("\n`{}`:\n```{}\n{}\n```\n", chunk.citation(), language, chunk.text));
+    }
+
+    let citations = chunks.iter().map(|chunk| chunk.citation()).collect();
+    Ok(Some((Message::new(MessageType::SYSTEM, content), citations)))
+}
+
+// File keeping the project attached to a conversation, next to the saved conversations
+fn project_file(conv_id: &Uuid) -> Result<PathBuf> {
+    Ok(StorageConfig::load()?.dir().join(format!("{}.project", conv_id)))
+}
+
+// Directory of the project attached to a conversation
+pub fn attached(conv_id: &Uuid) -> Result<Option<PathBuf>> {
+    let file = project_file(conv_id)?;
+    match fs::read_to_string(&file) {
+        Ok(contents) => Ok(Some(PathBuf::from(contents.trim()))),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(Error::Storage(format!("{}: {}", file.display(), e))),
+    }
+}
+
+// Attach a project directory to a conversation, replacing the previous one. Returns its
+// absolute path
+pub fn attach(conv_id: &Uuid, dir: &str) -> Result<PathBuf> {
+    let root = fs::canonicalize(dir).map_err(|e| Error::Config(format!("{}: {}", dir, e)))?;
+    if !root.is_dir() {
+        return Err(Error::Config(format!("{} is not a directory", dir)));
+    }
+
+    let file = project_file(conv_id)?;
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent).map_err(|e| Error::Storage(format!("{}: {}", parent.display(), e)))?;
+    }
+    fs::write(&file, root.to_string_lossy().as_bytes()).map_err(|e| Error::Storage(format!("{}: {}", file.display(), e)))?;
+    Ok(root)
+}
+
+// Nothing is done if no project is attached
+pub fn detach(conv_id: &Uuid) -> Result<()> {
+    let file = project_file(conv_id)?;
+    match fs::remove_file(&file) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(Error::Storage(format!("{}: {}", file.display(), e))),
+    }
+}
@@ -0,0 +1,270 @@
+use crate::app::modules::code::CodeBlock;
+use crate::error::{Error, Result};
+use crate::helper::config::load_config;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+// Limits of the code run from the answers
+pub const SANDBOX_CONFIG: &str = "config/sandbox.json";
+
+// How a language is run, inside its scratch directory
+#[derive(Deserialize, Debug, Clone)]
+pub struct LanguageConfig {
+    pub file: String,    // The code block is written to this file
+    pub command: String, // Shell command compiling and running it
+}
+
+// Content of `config/sandbox.json`
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct SandboxConfig {
+    pub timeout_secs: u64,    // Wall clock time before the process is killed
+    pub cpu_secs: u64,        // CPU time, compilation included
+    pub memory_mb: u64,       // Address space of each process
+    pub file_size_mb: u64,    // Largest file the code can write
+    pub max_output: usize,    // Bytes of stdout and stderr kept
+    pub network: bool,        // The process has no network unless this is true
+    pub scratch_dir: Option<String>, // Parent of the scratch directories, the system one by default
+    pub languages: HashMap<String, LanguageConfig>,
+    pub check_command: String, // Checks the Rust answers of the code module
+    pub repairs: usize,        // Times the code LLM is asked to fix Rust which doesn't compile, 0 disables the check
+}
+
+impl Default for SandboxConfig {
+    fn default() -> Self {
+        let language = |file: &str, command: &str| LanguageConfig {
+            file: file.to_string(),
+            command: command.to_string(),
+        };
+        Self {
+            timeout_secs: 10,
+            cpu_secs: 10,
+            memory_mb: 1024,
+            file_size_mb: 64,
+            max_output: 4000,
+            network: false,
+            scratch_dir: None,
+            languages: HashMap::from([
+                ("rust".to_string(), language("main.rs", "rustc --edition 2021 -o main main.rs && ./main")),
+                ("python".to_string(), language("main.py", "python3 main.py")),
+                ("sh".to_string(), language("main.sh", "sh main.sh")),
+            ]),
+            check_command: "cargo check --offline --quiet --message-format short".to_string(),
+            repairs: 2,
+        }
+    }
+}
+
+impl SandboxConfig {
+    pub fn load() -> Result<SandboxConfig> {
+        load_config(SANDBOX_CONFIG)
+    }
+
+    // Configuration of the language of a block, the usual names of a language are accepted
+    pub fn language(&self, block: &CodeBlock) -> Option<(&str, &LanguageConfig)> {
+        let language = match block.language.as_deref()? {
+            "rs" => "rust",
+            "py" | "python3" => "python",
+            "bash" | "shell" | "zsh" | "console" => "sh",
+            language => language,
+        };
+        self.languages.get_key_value(language).map(|(name, config)| (name.as_str(), config))
+    }
+}
+
+// What a run printed and how it ended
+pub struct RunOutput {
+    pub language: String,
+    pub status: Option<i32>, // Exit code, None when killed
+    pub timed_out: bool,
+    pub duration: Duration,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl RunOutput {
+    // Text of the system message added to the conversation
+    pub fn report(&self, number: usize) -> String {
+        let end = match (self.timed_out, self.status) {
+            (true, _) => format!("killed after {}s", self.duration.as_secs()),
+            (false, Some(code)) => format!("exit code {}", code),
+            (false, None) => "killed".to_string(),
+        };
+        let mut report = format!("Run of block {} ({}): {}, {} ms", number, self.language, end, self.duration.as_millis());
+        for (name, output) in [("stdout", &self.stdout), ("stderr", &self.stderr)] {
+            if !output.trim().is_empty() {
+                report.push_str(&format!("\n{}:\n```\n{}\n```", name, output.trim_end()));
+            }
+        }
+        report
+    }
+}
+
+// Directory removed with everything the code wrote in it, even if the run fails
+struct ScratchDir(PathBuf);
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+impl ScratchDir {
+    fn new(config: &SandboxConfig) -> Result<ScratchDir> {
+        let parent = config.scratch_dir.as_ref().map(PathBuf::from).unwrap_or_else(std::env::temp_dir);
+        let scratch = ScratchDir(parent.join(format!("nai-run-{}", Uuid::new_v4())));
+        fs::create_dir_all(&scratch.0).map_err(|e| sandbox_error(&scratch.0, e))?;
+        Ok(scratch)
+    }
+
+    fn write(&self, name: &str, contents: &str) -> Result<()> {
+        let file = self.0.join(name);
+        if let Some(dir) = file.parent() {
+            fs::create_dir_all(dir).map_err(|e| sandbox_error(dir, e))?;
+        }
+        fs::write(&file, contents).map_err(|e| sandbox_error(&file, e))
+    }
+}
+
+// Run a code block in a new scratch directory, without network and with the limits of the
+// configuration. Blocks the thread until the process ends or is killed
+pub fn run(block: &CodeBlock) -> Result<RunOutput> {
+    let config = SandboxConfig::load()?;
+    let (language, language_config) = config.language(block).ok_or(Error::Config(format!(
+        "{} code can't be run, {} has no command for it",
+        block.language.as_deref().unwrap_or("untagged"),
+        SANDBOX_CONFIG
+    )))?;
+
+    let scratch = ScratchDir::new(&config)?;
+    scratch.write(&language_config.file, &block.code)?;
+    run_command(&config, &scratch, language, &language_config.command)
+}
+
+// Check Rust code in a new cargo project, with the limits of `run`. Returns the diagnostics of
+// the compiler, None when the code compiles. Code without `main` is checked as a library
+pub fn cargo_check(code: &str) -> Result<Option<String>> {
+    let config = SandboxConfig::load()?;
+    let scratch = ScratchDir::new(&config)?;
+    scratch.write("Cargo.toml", "[package]\nname = \"snippet\"\nversion = \"0.1.0\"\nedition = \"2021\"\n")?;
+    let source = if code.contains("fn main") { "src/main.rs" } else { "src/lib.rs" };
+    scratch.write(source, code)?;
+
+    let output = run_command(&config, &scratch, "rust", &config.check_command)?;
+    if output.timed_out {
+        return Err(Error::Sandbox(format!("cargo check killed after {}s", output.duration.as_secs())));
+    }
+    // Cargo exits with 101 when the code doesn't compile, other statuses mean the check didn't
+    // run (cargo missing, no user namespaces...)
+    match output.status {
+        Some(0) => Ok(None),
+        Some(101) => Ok(Some(output.stderr.trim().to_string())),
+        status => Err(Error::Sandbox(format!(
+            "cargo check ended with {}: {}",
+            status.map_or("a signal".to_string(), |code| format!("exit code {}", code)),
+            output.stderr.trim()
+        ))),
+    }
+}
+
+// Run a shell command in the scratch directory, the limits are set by the shell before it so
+// they apply to the compiler too
+fn run_command(config: &SandboxConfig, scratch: &ScratchDir, language: &str, script: &str) -> Result<RunOutput> {
+    let script = format!(
+        "ulimit -t {} && ulimit -v {} && ulimit -f {} && {}",
+        config.cpu_secs,
+        config.memory_mb * 1024,
+        config.file_size_mb * 1024,
+        script
+    );
+    let mut command = if config.network {
+        Command::new("sh")
+    } else {
+        // A new network namespace only has a loopback interface, which is down
+        let mut command = Command::new("unshare");
+        command.args(["--net", "--map-root-user", "sh"]);
+        command
+    };
+    command
+        .args(["-c", &script])
+        .current_dir(&scratch.0)
+        .env("TMPDIR", &scratch.0)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .process_group(0); // The whole group is killed on timeout, compiler included
+
+    let start = Instant::now();
+    let mut child = command
+        .spawn()
+        .map_err(|e| Error::Config(format!("can't run {}: {}", language, e)))?;
+
+    // The outputs are read while the process runs, so it isn't blocked on a full pipe
+    let stdout = read_limited(child.stdout.take(), config.max_output);
+    let stderr = read_limited(child.stderr.take(), config.max_output);
+    let (status, timed_out) = wait(&mut child, Duration::from_secs(config.timeout_secs))?;
+
+    Ok(RunOutput {
+        language: language.to_string(),
+        status,
+        timed_out,
+        duration: start.elapsed(),
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+fn sandbox_error(path: &Path, e: std::io::Error) -> Error {
+    Error::Sandbox(format!("{}: {}", path.display(), e))
+}
+
+// Wait for the end of the process, its group is killed after `timeout`
+fn wait(child: &mut Child, timeout: Duration) -> Result<(Option<i32>, bool)> {
+    let start = Instant::now();
+    loop {
+        if let Some(status) = child.try_wait().map_err(|e| Error::Sandbox(e.to_string()))? {
+            return Ok((status.code(), false));
+        }
+        if start.elapsed() > timeout {
+            let _ = Command::new("kill").args(["-KILL", "--", &format!("-{}", child.id())]).status();
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok((None, true));
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+// Read an output until it's closed, only its first `limit` bytes are kept
+fn read_limited(output: Option<impl Read + Send + 'static>, limit: usize) -> thread::JoinHandle<String> {
+    thread::spawn(move || {
+        let Some(mut output) = output else {
+            return String::new();
+        };
+        let mut kept = Vec::new();
+        let mut truncated = false;
+        let mut buffer = [0u8; 4096];
+        while let Ok(read) = output.read(&mut buffer) {
+            if read == 0 {
+                break;
+            }
+            let room = limit.saturating_sub(kept.len());
+            kept.extend_from_slice(&buffer[..read.min(room)]);
+            truncated |= read > room;
+        }
+
+        let mut text = String::from_utf8_lossy(&kept).to_string();
+        if truncated {
+            text.push_str("\n[output truncated]");
+        }
+        text
+    })
+}
@@ -0,0 +1,114 @@
+use crate::app::conversation;
+use crate::app::index::{self, InvertedIndex};
+use crate::app::llm::MessageType;
+use crate::error::Result;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+// Characters shown around the first match of a hit
+const SNIPPET_CONTEXT: usize = 40;
+
+// Maximum number of hits returned
+const MAX_HITS: usize = 50;
+
+// A message matching the query
+pub struct SearchHit {
+    pub conv_id: Uuid,
+    pub conv_title: String,
+    pub index: usize, // Position of the message in the conversation
+    pub role: MessageType,
+    pub snippet: String,
+}
+
+// A message of a saved conversation, as indexed
+struct Document {
+    conv_id: Uuid,
+    index: usize,
+    role: MessageType,
+    content: String,
+}
+
+// Inverted index of every message of every saved conversation
+pub struct SearchIndex {
+    documents: InvertedIndex<Document>,
+    titles: HashMap<Uuid, String>,
+}
+
+impl SearchIndex {
+    // Index the conversations saved in the conversation directory
+    pub fn build() -> Result<SearchIndex> {
+        let mut index = SearchIndex {
+            documents: InvertedIndex::new(),
+            titles: HashMap::new(),
+        };
+
+        for conv in conversation::list()? {
+            for (i, message) in conversation::load(&conv.id)?.into_iter().enumerate() {
+                let text = message.content.clone();
+                let document = Document {
+                    conv_id: conv.id,
+                    index: i,
+                    role: message.role,
+                    content: message.content,
+                };
+                index.documents.add(document, &text);
+            }
+            index.titles.insert(conv.id, conv.title);
+        }
+        Ok(index)
+    }
+
+    // Messages matching at least one word of the query, the best ranked first
+    pub fn search(&self, query: &str) -> Vec<SearchHit> {
+        let query_terms = index::terms(query);
+        self.documents
+            .search(query)
+            .into_iter()
+            .take(MAX_HITS)
+            .map(|(document, _)| SearchHit {
+                conv_id: document.conv_id,
+                conv_title: self.titles.get(&document.conv_id).cloned().unwrap_or_default(),
+                index: document.index,
+                role: document.role.clone(),
+                snippet: snippet(&document.content, &query_terms),
+            })
+            .collect()
+    }
+}
+
+// Search every saved conversation
+pub fn search(query: &str) -> Result<Vec<SearchHit>> {
+    Ok(SearchIndex::build()?.search(query))
+}
+
+// Part of the content around the first word of the query found, on one line
+fn snippet(content: &str, query_terms: &[String]) -> String {
+    let chars: Vec<char> = content.chars().collect();
+    let lowercase: Vec<char> = content.chars().flat_map(|c| c.to_lowercase()).collect();
+
+    // Lowercase may change the number of chars, in that case the snippet starts at the beginning
+    let position = if lowercase.len() == chars.len() {
+        let lowercase: String = lowercase.into_iter().collect();
+        query_terms
+            .iter()
+            .filter_map(|term| lowercase.find(term.as_str()))
+            .min()
+            .map(|byte| lowercase[..byte].chars().count())
+            .unwrap_or(0)
+    } else {
+        0
+    };
+
+    let start = position.saturating_sub(SNIPPET_CONTEXT);
+    let end = (position + SNIPPET_CONTEXT * 2).min(chars.len());
+
+    let mut snippet: String = chars[start..end].iter().collect();
+    snippet = snippet.split_whitespace().collect::<Vec<_>>().join(" ");
+    if start > 0 {
+        snippet.insert_str(0, "...");
+    }
+    if end < chars.len() {
+        snippet.push_str("...");
+    }
+    snippet
+}
@@ -0,0 +1,3 @@
+pub mod init;
+pub mod jsonl;
+pub mod sqlite;
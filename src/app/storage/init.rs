@@ -0,0 +1,179 @@
+use crate::app::llm::Message;
+use crate::app::storage::{jsonl::JsonlStorage, sqlite::SqliteStorage};
+use crate::error::Result;
+use crate::helper::config::load_config;
+use crate::helper::init::warn;
+use chrono::{DateTime, Local};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use uuid::Uuid;
+
+// Configuration of the storage, the JSONL files are used when it doesn't exist
+pub const STORAGE_CONFIG: &str = "config/storage.json";
+
+// Directory of the JSONL storage, one file per conversation
+pub const CONV_DIR: &str = "conv";
+
+// Database of the SQLite storage when no path is configured
+pub const DEFAULT_DATABASE: &str = "conv.db";
+
+// Where the conversations are saved. A conversation is identified by an uuid and made of its
+// messages in order, with an optional title and tags
+pub trait Storage: Send + Sync {
+    // Id of every saved conversation, in no particular order
+    fn ids(&self) -> Result<Vec<Uuid>>;
+
+    fn load(&self, conv_id: &Uuid) -> Result<Vec<Message>>;
+
+    // Add a message at the end of a conversation, the conversation is created if needed
+    fn append(&self, conv_id: &Uuid, message: &Message) -> Result<()>;
+
+    // Save every message of a conversation at once, used to copy conversations
+    fn import(&self, conv_id: &Uuid, messages: &[Message]) -> Result<()> {
+        messages.iter().try_for_each(|message| self.append(conv_id, message))
+    }
+
+    // Last time a message was saved in the conversation
+    fn updated(&self, conv_id: &Uuid) -> Result<DateTime<Local>>;
+
+    fn title(&self, conv_id: &Uuid) -> Result<Option<String>>;
+
+    // The conversation must exist, it isn't created by its title
+    fn rename(&self, conv_id: &Uuid, title: &str) -> Result<()>;
+
+    fn tags(&self, conv_id: &Uuid) -> Result<Vec<String>>;
+
+    fn set_tags(&self, conv_id: &Uuid, tags: &[String]) -> Result<()>;
+
+    // Remove a conversation with its title and tags, nothing is done if it doesn't exist
+    fn delete(&self, conv_id: &Uuid) -> Result<()>;
+}
+
+#[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageKind {
+    #[default]
+    Jsonl,  // One JSON line per message in `conv/<id>`
+    Sqlite, // Embedded database
+}
+
+// Content of `config/storage.json`
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct StorageConfig {
+    pub backend: StorageKind,
+    pub path: Option<String>, // Directory of the JSONL files or file of the SQLite database
+}
+
+impl StorageConfig {
+    pub fn load() -> Result<StorageConfig> {
+        load_config(STORAGE_CONFIG)
+    }
+
+    pub fn database(&self) -> &str {
+        self.path.as_deref().unwrap_or(DEFAULT_DATABASE)
+    }
+
+    // Directory of the files made from the conversations: the JSONL directory, or `<database
+    // name>.d` next to the SQLite database so they aren't mixed with the working directory
+    pub fn dir(&self) -> PathBuf {
+        match self.backend {
+            StorageKind::Jsonl => PathBuf::from(self.path.as_deref().unwrap_or(CONV_DIR)),
+            StorageKind::Sqlite => Path::new(self.database()).with_extension("d"),
+        }
+    }
+}
+
+static STORAGE: OnceLock<Box<dyn Storage>> = OnceLock::new();
+
+// Storage chosen by the configuration, opened on first use
+pub fn open() -> Result<&'static dyn Storage> {
+    if let Some(storage) = STORAGE.get() {
+        return Ok(storage.as_ref());
+    }
+
+    let config = StorageConfig::load()?;
+    let storage: Box<dyn Storage> = match config.backend {
+        StorageKind::Jsonl => Box::new(JsonlStorage::new(config.path.as_deref().unwrap_or(CONV_DIR))),
+        StorageKind::Sqlite => {
+            let created = !Path::new(config.database()).exists();
+            let storage = SqliteStorage::open(config.database())?;
+
+            // The conversations saved as JSONL files before are imported in the new database
+            if created {
+                let nb_copied = migrate(&JsonlStorage::new(CONV_DIR), &storage)?;
+                warn(format!("{} conversations imported from {} to {}", nb_copied, CONV_DIR, config.database()));
+            }
+            Box::new(storage)
+        }
+    };
+    Ok(STORAGE.get_or_init(|| storage).as_ref())
+}
+
+// Copy the conversations of `from` missing in `to`, returns the number of conversations copied.
+// Running it again only copies the conversations created since
+pub fn migrate(from: &dyn Storage, to: &dyn Storage) -> Result<usize> {
+    let existing = to.ids()?;
+
+    let mut nb_copied = 0;
+    for conv_id in from.ids()? {
+        if existing.contains(&conv_id) {
+            continue;
+        }
+
+        to.import(&conv_id, &from.load(&conv_id)?)?;
+        if let Some(title) = from.title(&conv_id)? {
+            to.rename(&conv_id, &title)?;
+        }
+        let tags = from.tags(&conv_id)?;
+        if !tags.is_empty() {
+            to.set_tags(&conv_id, &tags)?;
+        }
+        nb_copied += 1;
+    }
+    Ok(nb_copied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::llm::MessageType;
+
+    #[test]
+    fn migrate_keeps_the_order_and_the_branches() {
+        let dir = std::env::temp_dir().join(format!("nai-migrate-{}", Uuid::new_v4()));
+        let jsonl = JsonlStorage::new(&dir.to_string_lossy());
+        let sqlite = SqliteStorage::open(":memory:").unwrap();
+
+        // An edited question and its answer make a second branch
+        let conv_id = Uuid::new_v4();
+        let mut edit = Message::new(MessageType::USER, "question, edited".to_string());
+        edit.meta.edit_of = Some(0);
+        let mut answer = Message::new(MessageType::ASSISTANT, "second answer".to_string());
+        answer.meta.parent = Some(2);
+        let messages = [
+            Message::new(MessageType::USER, "question".to_string()),
+            Message::new(MessageType::ASSISTANT, "first answer".to_string()),
+            edit,
+            answer,
+        ];
+        jsonl.import(&conv_id, &messages).unwrap();
+        jsonl.rename(&conv_id, "Branches").unwrap();
+        jsonl.set_tags(&conv_id, &["test".to_string()]).unwrap();
+
+        assert_eq!(migrate(&jsonl, &sqlite).unwrap(), 1);
+
+        let migrated = sqlite.load(&conv_id).unwrap();
+        let contents: Vec<&str> = migrated.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, ["question", "first answer", "question, edited", "second answer"]);
+        let links: Vec<_> = migrated.iter().map(|m| (m.meta.edit_of, m.meta.parent)).collect();
+        assert_eq!(links, [(None, None), (None, None), (Some(0), None), (None, Some(2))]);
+        assert_eq!(sqlite.title(&conv_id).unwrap().as_deref(), Some("Branches"));
+        assert_eq!(sqlite.tags(&conv_id).unwrap(), ["test"]);
+
+        // Only the conversations created since are copied again
+        assert_eq!(migrate(&jsonl, &sqlite).unwrap(), 0);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,151 @@
+use crate::app::llm::{Message, MessageType};
+use crate::app::storage::init::Storage;
+use crate::error::{Error, Result};
+use crate::helper::init::warn;
+use chrono::{DateTime, Local};
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::Path;
+use uuid::Uuid;
+
+// Conversations saved as files in a directory: `<id>` has one JSON line per message,
+// `<id>.title` the title and `<id>.tags` one tag per line
+pub struct JsonlStorage {
+    dir: String,
+}
+
+fn storage_error(path: &str, e: std::io::Error) -> Error {
+    Error::Storage(format!("{}: {}", path, e))
+}
+
+// Remove a file, it's fine if it doesn't exist
+fn remove(path: &str) -> Result<()> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(storage_error(path, e)),
+    }
+}
+
+// Content of a file, None if it doesn't exist
+fn read(path: &str) -> Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(storage_error(path, e)),
+    }
+}
+
+impl JsonlStorage {
+    pub fn new(dir: &str) -> JsonlStorage {
+        JsonlStorage { dir: dir.to_string() }
+    }
+
+    fn path(&self, conv_id: &Uuid) -> String {
+        format!("{}/{}", self.dir, conv_id)
+    }
+
+    fn title_path(&self, conv_id: &Uuid) -> String {
+        format!("{}/{}.title", self.dir, conv_id)
+    }
+
+    fn tags_path(&self, conv_id: &Uuid) -> String {
+        format!("{}/{}.tags", self.dir, conv_id)
+    }
+
+    fn write(&self, path: &str, contents: &str) -> Result<()> {
+        fs::create_dir_all(&self.dir).map_err(|e| storage_error(&self.dir, e))?;
+        fs::write(path, contents).map_err(|e| storage_error(path, e))
+    }
+}
+
+impl Storage for JsonlStorage {
+    fn ids(&self) -> Result<Vec<Uuid>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            // Nothing has been saved yet
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(storage_error(&self.dir, e)),
+        };
+
+        // Title, tags and other files aren't conversations
+        Ok(entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| Uuid::parse_str(&entry.file_name().to_string_lossy()).ok())
+            .collect())
+    }
+
+    fn load(&self, conv_id: &Uuid) -> Result<Vec<Message>> {
+        let path = self.path(conv_id);
+        let contents = fs::read_to_string(&path).map_err(|e| storage_error(&path, e))?;
+
+        let mut messages = Vec::new();
+        for (i, line) in contents.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+            // A broken line (e.g. the app was killed while writing) doesn't prevent loading the
+            // rest. It's replaced by a system message, the branches link messages by position
+            match serde_json::from_str::<Message>(line) {
+                Ok(message) => messages.push(message),
+                Err(e) => {
+                    warn(format!("{}:{} unreadable: {}", path, i + 1, e));
+                    messages.push(Message::new(MessageType::SYSTEM, format!("[unreadable message: {}]", e)));
+                }
+            }
+        }
+        Ok(messages)
+    }
+
+    fn append(&self, conv_id: &Uuid, message: &Message) -> Result<()> {
+        let path = self.path(conv_id);
+
+        // Create conv directory if doesn't exist
+        fs::create_dir_all(&self.dir).map_err(|e| storage_error(&self.dir, e))?;
+
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&path)
+            .map_err(|e| storage_error(&path, e))?;
+
+        writeln!(file, "{}", serde_json::to_string(message)?).map_err(|e| storage_error(&path, e))
+    }
+
+    fn updated(&self, conv_id: &Uuid) -> Result<DateTime<Local>> {
+        let path = self.path(conv_id);
+        fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .map(DateTime::<Local>::from)
+            .map_err(|e| storage_error(&path, e))
+    }
+
+    fn title(&self, conv_id: &Uuid) -> Result<Option<String>> {
+        Ok(read(&self.title_path(conv_id))?
+            .map(|title| title.trim().to_string())
+            .filter(|title| !title.is_empty()))
+    }
+
+    fn rename(&self, conv_id: &Uuid, title: &str) -> Result<()> {
+        if !Path::new(&self.path(conv_id)).exists() {
+            return Err(Error::Storage(format!("no conversation {}", conv_id)));
+        }
+        self.write(&self.title_path(conv_id), title.trim())
+    }
+
+    fn tags(&self, conv_id: &Uuid) -> Result<Vec<String>> {
+        Ok(read(&self.tags_path(conv_id))?
+            .map(|tags| tags.lines().map(|tag| tag.trim().to_string()).filter(|tag| !tag.is_empty()).collect())
+            .unwrap_or_default())
+    }
+
+    fn set_tags(&self, conv_id: &Uuid, tags: &[String]) -> Result<()> {
+        if tags.is_empty() {
+            return remove(&self.tags_path(conv_id));
+        }
+        self.write(&self.tags_path(conv_id), &tags.join("\n"))
+    }
+
+    fn delete(&self, conv_id: &Uuid) -> Result<()> {
+        remove(&self.path(conv_id))?;
+        remove(&self.title_path(conv_id))?;
+        remove(&self.tags_path(conv_id))
+    }
+}
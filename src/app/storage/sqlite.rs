@@ -0,0 +1,278 @@
+use crate::app::llm::{Message, MessageMeta, MessageType};
+use crate::app::storage::init::Storage;
+use crate::error::{Error, Result};
+use chrono::{DateTime, Local};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use uuid::Uuid;
+
+// Tables of the database, created on first use
+const SCHEMA: &str = "
+    PRAGMA foreign_keys = ON;
+    CREATE TABLE IF NOT EXISTS conversations (
+        id TEXT PRIMARY KEY,
+        title TEXT,
+        created TEXT NOT NULL,
+        updated TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS messages (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        conv_id TEXT NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        role TEXT NOT NULL,
+        content TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS messages_conv ON messages(conv_id, position);
+    CREATE TABLE IF NOT EXISTS metadata (
+        message_id INTEGER PRIMARY KEY REFERENCES messages(id) ON DELETE CASCADE,
+        timestamp TEXT,
+        meta TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS tags (
+        conv_id TEXT NOT NULL REFERENCES conversations(id) ON DELETE CASCADE,
+        tag TEXT NOT NULL,
+        PRIMARY KEY (conv_id, tag)
+    );
+";
+
+// Conversations saved in an embedded SQLite database. The metadata of a message is kept as
+// JSON so new fields don't need a new column
+pub struct SqliteStorage {
+    connection: Mutex<Connection>,
+}
+
+fn parse_date(date: &str) -> Result<DateTime<Local>> {
+    DateTime::parse_from_rfc3339(date)
+        .map(|date| date.with_timezone(&Local))
+        .map_err(|e| Error::Storage(format!("invalid date {}: {}", date, e)))
+}
+
+// Same role names as in the JSONL files
+fn role_name(role: &MessageType) -> Result<String> {
+    Ok(serde_json::to_value(role)?.as_str().unwrap_or_default().to_string())
+}
+
+impl SqliteStorage {
+    pub fn open(path: &str) -> Result<SqliteStorage> {
+        // SQLite creates the file but not its directory
+        if let Some(dir) = Path::new(path).parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|e| Error::Storage(format!("{}: {}", dir.display(), e)))?;
+        }
+        let connection = Connection::open(path)
+            .map_err(|e| Error::Storage(format!("{}: {}", path, e)))?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(SqliteStorage { connection: Mutex::new(connection) })
+    }
+
+    fn connection(&self) -> Result<MutexGuard<'_, Connection>> {
+        self.connection
+            .lock()
+            .map_err(|_| Error::Storage("database connection poisoned".to_string()))
+    }
+
+    // Create the conversation if needed and set its last update
+    fn touch(connection: &Connection, conv_id: &Uuid, date: &DateTime<Local>) -> Result<()> {
+        connection.execute(
+            "INSERT INTO conversations (id, created, updated) VALUES (?1, ?2, ?2)
+             ON CONFLICT(id) DO UPDATE SET updated = excluded.updated",
+            params![conv_id.to_string(), date.to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    // Create the conversation if needed, without changing its last update
+    fn create(connection: &Connection, conv_id: &Uuid) -> Result<()> {
+        connection.execute(
+            "INSERT OR IGNORE INTO conversations (id, created, updated) VALUES (?1, ?2, ?2)",
+            params![conv_id.to_string(), Local::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    fn insert(connection: &Connection, conv_id: &Uuid, message: &Message) -> Result<()> {
+        connection.execute(
+            "INSERT INTO messages (conv_id, position, role, content)
+             VALUES (?1, (SELECT COUNT(*) FROM messages WHERE conv_id = ?1), ?2, ?3)",
+            params![conv_id.to_string(), role_name(&message.role)?, message.content],
+        )?;
+        connection.execute(
+            "INSERT INTO metadata (message_id, timestamp, meta) VALUES (?1, ?2, ?3)",
+            params![
+                connection.last_insert_rowid(),
+                message.meta.timestamp.map(|date| date.to_rfc3339()),
+                serde_json::to_string(&message.meta)?
+            ],
+        )?;
+        Ok(())
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn ids(&self) -> Result<Vec<Uuid>> {
+        let connection = self.connection()?;
+        let mut statement = connection.prepare("SELECT id FROM conversations")?;
+        let ids = statement
+            .query_map([], |row| row.get::<_, String>(0))?
+            .filter_map(|id| id.ok())
+            .filter_map(|id| Uuid::parse_str(&id).ok())
+            .collect();
+        Ok(ids)
+    }
+
+    fn load(&self, conv_id: &Uuid) -> Result<Vec<Message>> {
+        let connection = self.connection()?;
+        let exists = connection
+            .query_row("SELECT 1 FROM conversations WHERE id = ?1", [conv_id.to_string()], |_| Ok(()))
+            .optional()?;
+        if exists.is_none() {
+            return Err(Error::Storage(format!("no conversation {}", conv_id)));
+        }
+
+        let mut statement = connection.prepare(
+            "SELECT role, content, meta FROM messages
+             LEFT JOIN metadata ON metadata.message_id = messages.id
+             WHERE conv_id = ?1 ORDER BY position",
+        )?;
+        let rows = statement.query_map([conv_id.to_string()], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, Option<String>>(2)?))
+        })?;
+
+        let mut messages = Vec::new();
+        for row in rows {
+            let (role, content, meta) = row?;
+            let role: MessageType = serde_json::from_value(Value::String(role))?;
+            let meta: MessageMeta = match meta {
+                Some(meta) => serde_json::from_str(&meta)?,
+                None => MessageMeta::default(),
+            };
+            messages.push(Message { role, content, meta, position: None });
+        }
+        Ok(messages)
+    }
+
+    fn append(&self, conv_id: &Uuid, message: &Message) -> Result<()> {
+        let mut connection = self.connection()?;
+        let transaction = connection.transaction()?;
+        Self::touch(&transaction, conv_id, &Local::now())?;
+        Self::insert(&transaction, conv_id, message)?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    // Everything is written in one transaction, the last update is the date of the last message
+    fn import(&self, conv_id: &Uuid, messages: &[Message]) -> Result<()> {
+        let updated = messages
+            .iter()
+            .rev()
+            .find_map(|message| message.meta.timestamp)
+            .unwrap_or_else(Local::now);
+
+        let mut connection = self.connection()?;
+        let transaction = connection.transaction()?;
+        Self::touch(&transaction, conv_id, &updated)?;
+        for message in messages {
+            Self::insert(&transaction, conv_id, message)?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn updated(&self, conv_id: &Uuid) -> Result<DateTime<Local>> {
+        let updated: String = self.connection()?.query_row(
+            "SELECT updated FROM conversations WHERE id = ?1",
+            [conv_id.to_string()],
+            |row| row.get(0),
+        )?;
+        parse_date(&updated)
+    }
+
+    fn title(&self, conv_id: &Uuid) -> Result<Option<String>> {
+        let title: Option<Option<String>> = self
+            .connection()?
+            .query_row(
+                "SELECT title FROM conversations WHERE id = ?1",
+                [conv_id.to_string()],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(title.flatten().filter(|title| !title.is_empty()))
+    }
+
+    fn rename(&self, conv_id: &Uuid, title: &str) -> Result<()> {
+        let updated = self.connection()?.execute(
+            "UPDATE conversations SET title = ?2 WHERE id = ?1",
+            params![conv_id.to_string(), title.trim()],
+        )?;
+        if updated == 0 {
+            return Err(Error::Storage(format!("no conversation {}", conv_id)));
+        }
+        Ok(())
+    }
+
+    fn tags(&self, conv_id: &Uuid) -> Result<Vec<String>> {
+        let connection = self.connection()?;
+        let mut statement = connection.prepare("SELECT tag FROM tags WHERE conv_id = ?1 ORDER BY tag")?;
+        let tags = statement
+            .query_map([conv_id.to_string()], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        Ok(tags)
+    }
+
+    fn set_tags(&self, conv_id: &Uuid, tags: &[String]) -> Result<()> {
+        let mut connection = self.connection()?;
+        let transaction = connection.transaction()?;
+        Self::create(&transaction, conv_id)?;
+        transaction.execute("DELETE FROM tags WHERE conv_id = ?1", [conv_id.to_string()])?;
+        for tag in tags {
+            transaction.execute(
+                "INSERT OR IGNORE INTO tags (conv_id, tag) VALUES (?1, ?2)",
+                params![conv_id.to_string(), tag],
+            )?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn delete(&self, conv_id: &Uuid) -> Result<()> {
+        self.connection()?
+            .execute("DELETE FROM conversations WHERE id = ?1", [conv_id.to_string()])?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::llm::MessageType;
+
+    #[test]
+    fn conversations_are_saved_renamed_tagged_and_deleted() {
+        let storage = SqliteStorage::open(":memory:").unwrap();
+        let conv_id = Uuid::new_v4();
+        assert!(storage.rename(&conv_id, "Not saved yet").is_err());
+
+        let mut answer = Message::new(MessageType::ASSISTANT, "Hi".to_string());
+        answer.meta.model = Some("model".to_string());
+        storage.append(&conv_id, &Message::new(MessageType::USER, "Hello".to_string())).unwrap();
+        storage.append(&conv_id, &answer).unwrap();
+
+        let messages = storage.load(&conv_id).unwrap();
+        assert_eq!(messages.iter().map(|m| m.content.as_str()).collect::<Vec<_>>(), ["Hello", "Hi"]);
+        assert!(matches!(messages[0].role, MessageType::USER));
+        assert_eq!(messages[1].meta.model.as_deref(), Some("model"));
+        assert_eq!(storage.ids().unwrap(), [conv_id]);
+
+        storage.rename(&conv_id, " Greetings ").unwrap();
+        storage.set_tags(&conv_id, &["b".to_string(), "a".to_string()]).unwrap();
+        assert_eq!(storage.title(&conv_id).unwrap().as_deref(), Some("Greetings"));
+        assert_eq!(storage.tags(&conv_id).unwrap(), ["a", "b"]);
+
+        storage.delete(&conv_id).unwrap();
+        assert!(storage.ids().unwrap().is_empty());
+        assert!(storage.load(&conv_id).is_err());
+        assert!(storage.tags(&conv_id).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,155 @@
+use crate::app::llm::Message;
+
+// Every saved message of a conversation, with all its branches. Messages are saved one after
+// the other, a message follows the one saved before it unless its `edit_of` or `parent` metadata
+// says otherwise, so the files written before branches existed are a single branch
+#[derive(Default)]
+pub struct ConvTree {
+    messages: Vec<Message>,
+    parents: Vec<Option<usize>>, // Previous message of the branch, None for the first one
+}
+
+impl ConvTree {
+    pub fn new(messages: Vec<Message>) -> ConvTree {
+        let mut tree = ConvTree::default();
+        for message in messages {
+            tree.push(message);
+        }
+        tree
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    // Add a message saved at the end of the conversation, returns its position
+    pub fn push(&mut self, message: Message) -> usize {
+        let position = self.messages.len();
+
+        // Links to a later message are broken, the message follows the previous one instead
+        let parent = match (message.meta.edit_of, message.meta.parent) {
+            (Some(edited), _) if edited < position => self.parents[edited],
+            (_, Some(parent)) if parent < position => Some(parent),
+            _ => position.checked_sub(1),
+        };
+
+        self.parents.push(parent);
+        self.messages.push(message);
+        position
+    }
+
+    // Messages from the start of the conversation to `leaf`, with their position
+    pub fn branch(&self, leaf: usize) -> Vec<Message> {
+        let mut branch = Vec::new();
+        let mut current = Some(leaf).filter(|leaf| *leaf < self.len());
+        while let Some(position) = current {
+            let mut message = self.messages[position].clone();
+            message.position = Some(position);
+            branch.push(message);
+            current = self.parents[position];
+        }
+        branch.reverse();
+        branch
+    }
+
+    // Branch ending with the last saved message, the one shown when a conversation is opened
+    pub fn current(&self) -> Vec<Message> {
+        match self.len() {
+            0 => Vec::new(),
+            len => self.branch(len - 1),
+        }
+    }
+
+    // End of the most recent branch going through `position`
+    pub fn last_leaf(&self, position: usize) -> usize {
+        let mut leaf = position;
+        while let Some(child) = (leaf + 1..self.len()).rev().find(|i| self.parents[*i] == Some(leaf)) {
+            leaf = child;
+        }
+        leaf
+    }
+
+    // Messages following the same message as `position`, including it, oldest first
+    pub fn siblings(&self, position: usize) -> Vec<usize> {
+        let Some(parent) = self.parents.get(position) else {
+            return Vec::new();
+        };
+        (0..self.len()).filter(|i| self.parents[*i] == *parent).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::app::llm::MessageType;
+
+    fn message(content: &str, parent: Option<usize>, edit_of: Option<usize>) -> Message {
+        let role = if content.starts_with('q') { MessageType::USER } else { MessageType::ASSISTANT };
+        let mut message = Message::new(role, content.to_string());
+        message.meta.parent = parent;
+        message.meta.edit_of = edit_of;
+        message
+    }
+
+    fn contents(messages: &[Message]) -> Vec<&str> {
+        messages.iter().map(|m| m.content.as_str()).collect()
+    }
+
+    // q1 a1 q2 a2, then q2 edited as q2b answered by a2b, then a2 answered again from q2
+    fn tree() -> ConvTree {
+        ConvTree::new(vec![
+            message("q1", None, None),
+            message("a1", None, None),
+            message("q2", None, None),
+            message("a2", None, None),
+            message("q2b", None, Some(2)),
+            message("a2b", None, None),
+            message("a2c", Some(2), None),
+        ])
+    }
+
+    #[test]
+    fn messages_without_links_are_one_branch() {
+        let tree = ConvTree::new(vec![message("q1", None, None), message("a1", None, None)]);
+        let current = tree.current();
+        assert_eq!(contents(&current), ["q1", "a1"]);
+        assert_eq!(current.iter().map(|m| m.position).collect::<Vec<_>>(), [Some(0), Some(1)]);
+        assert!(ConvTree::default().current().is_empty());
+    }
+
+    #[test]
+    fn edits_and_parents_make_branches() {
+        let tree = tree();
+        assert_eq!(contents(&tree.current()), ["q1", "a1", "q2", "a2c"]);
+        assert_eq!(contents(&tree.branch(5)), ["q1", "a1", "q2b", "a2b"]);
+        assert_eq!(contents(&tree.branch(3)), ["q1", "a1", "q2", "a2"]);
+    }
+
+    #[test]
+    fn siblings_are_the_versions_of_a_message() {
+        let tree = tree();
+        assert_eq!(tree.siblings(2), [2, 4]);
+        assert_eq!(tree.siblings(3), [3, 6]);
+        assert_eq!(tree.siblings(0), [0]);
+        assert!(tree.siblings(42).is_empty());
+    }
+
+    #[test]
+    fn last_leaf_follows_the_most_recent_branch() {
+        let tree = tree();
+        assert_eq!(tree.last_leaf(2), 6);
+        assert_eq!(tree.last_leaf(4), 5);
+        // The most recent version of each message is followed
+        assert_eq!(tree.last_leaf(0), 5);
+    }
+
+    #[test]
+    fn links_to_later_messages_are_ignored() {
+        let tree = ConvTree::new(vec![
+            message("q1", None, None),
+            message("a1", Some(5), None),
+            message("q2", None, Some(7)),
+        ]);
+        assert_eq!(contents(&tree.current()), ["q1", "a1", "q2"]);
+    }
+}
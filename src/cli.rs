@@ -0,0 +1,93 @@
+use crate::app::conversation;
+use crate::app::export::{self, ExportRequest};
+use crate::app::importer::{self, Source};
+use crate::app::llm::MessageType;
+use crate::app::search;
+use crate::app::storage::init::{migrate, StorageConfig, StorageKind, CONV_DIR};
+use crate::app::storage::{jsonl::JsonlStorage, sqlite::SqliteStorage};
+use crate::error::{Error, Result};
+
+// Subcommands run without the UI, returns false if the arguments aren't a subcommand
+pub fn run(args: &[String]) -> Result<bool> {
+    match args.first().map(|arg| arg.as_str()) {
+        Some("search") => print_search(&args[1..].join(" "))?,
+        Some("migrate") => migrate_to_sqlite()?,
+        Some("export") => print_export(&args[1..])?,
+        Some("import") => print_import(&args[1..])?,
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+fn print_search(query: &str) -> Result<()> {
+    let hits = search::search(query)?;
+    if hits.is_empty() {
+        println!("No message matching \"{}\"", query);
+    }
+
+    for hit in hits {
+        let role = match hit.role {
+            MessageType::USER => "You",
+            MessageType::ASSISTANT => "Néo AI",
+            MessageType::SYSTEM => "System",
+        };
+        println!("{} #{} {}", hit.conv_id, hit.index, hit.conv_title);
+        println!("    {}: {}", role, hit.snippet);
+    }
+    Ok(())
+}
+
+// `export <id> [md|html|json] [--resume] [--sources] [-o <path>]`, printed when no path is given
+fn print_export(args: &[String]) -> Result<()> {
+    let prefix = args.first().map(|arg| arg.as_str()).unwrap_or("");
+    let conv_id = conversation::resolve(prefix)?;
+
+    let options: Vec<&str> = args.iter().skip(1).map(|arg| arg.as_str()).collect();
+    let request = ExportRequest::parse(&options)?;
+    let rendered = export::export(&conv_id, &request)?;
+
+    match &request.output {
+        Some(path) => println!("Conversation exported to {}", path),
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
+
+// `import <file> [--format chatgpt|openwebui|markdown|nai]`, the format is guessed by default
+fn print_import(args: &[String]) -> Result<()> {
+    let mut path = None;
+    let mut source = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => {
+                let name = args.next().ok_or(Error::Config("--format needs a format".to_string()))?;
+                source = Some(Source::parse(name)?);
+            }
+            arg => path = Some(arg),
+        }
+    }
+    let path = path.ok_or(Error::Config("give the file to import".to_string()))?;
+
+    let imported = importer::import_file(path, source)?;
+    for conv in &imported {
+        println!("{} {} ({} messages)", conv.id, conv.title, conv.nb_messages);
+    }
+    println!("{} conversations imported from {}", imported.len(), path);
+    Ok(())
+}
+
+// Import the conversations saved as JSONL files in the configured SQLite database, the ones
+// already imported are skipped
+fn migrate_to_sqlite() -> Result<()> {
+    let config = StorageConfig::load()?;
+    if config.backend != StorageKind::Sqlite {
+        return Err(Error::Config("set \"backend\": \"sqlite\" in config/storage.json to migrate".to_string()));
+    }
+
+    let database = SqliteStorage::open(config.database())?;
+    let nb_copied = migrate(&JsonlStorage::new(CONV_DIR), &database)?;
+    println!("{} conversations imported from {}/ to {}", nb_copied, CONV_DIR, config.database());
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use std::fmt;
+
+// Errors of Néo AI, they are shown to the user as system messages instead of crashing the UI
+#[derive(Debug)]
+pub enum Error {
+    Config(String),    // Missing or invalid configuration file
+    Transport(String), // Unable to reach a server or read its response
+    Backend(String),   // Error reported by the LLM server (unknown model, context overflow...)
+    Parse(String),     // Answer not in the expected format
+    Retrieval(String), // No data found by a module (Wikipedia search...)
+    Storage(String),   // Unable to read or write the conversations
+    Sandbox(String),   // Code of an answer which couldn't be run to its end
+    Terminal(String),  // Unable to write to the terminal (clipboard...)
+    Runtime(String),   // Unable to start the threads running the pipelines
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Config(e) => write!(f, "Configuration error: {}", e),
+            Error::Transport(e) => write!(f, "Connection error: {}", e),
+            Error::Backend(e) => write!(f, "LLM server error: {}", e),
+            Error::Parse(e) => write!(f, "Unexpected answer: {}", e),
+            Error::Retrieval(e) => write!(f, "Retrieval error: {}", e),
+            Error::Storage(e) => write!(f, "Storage error: {}", e),
+            Error::Sandbox(e) => write!(f, "Sandbox error: {}", e),
+            Error::Terminal(e) => write!(f, "Terminal error: {}", e),
+            Error::Runtime(e) => write!(f, "Runtime error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Transport(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Parse(e.to_string())
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(e: rusqlite::Error) -> Self {
+        Error::Storage(e.to_string())
+    }
+}
@@ -1 +1,3 @@
+pub mod clipboard;
+pub mod config;
 pub mod init;
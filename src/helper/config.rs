@@ -0,0 +1,13 @@
+use crate::error::{Error, Result};
+use serde::de::DeserializeOwned;
+use std::fs;
+use std::io::ErrorKind;
+
+// Read an optional JSON configuration file, the defaults are used when it doesn't exist
+pub fn load_config<T: DeserializeOwned + Default>(path: &str) -> Result<T> {
+    match fs::read_to_string(path) {
+        Ok(contents) => serde_json::from_str(&contents).map_err(|e| Error::Config(format!("{}: {}", path, e))),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(Error::Config(format!("{}: {}", path, e))),
+    }
+}
@@ -2,13 +2,15 @@ use chrono::prelude::*;
 use std::fs::OpenOptions;
 use std::io::Write;
 
+// Logging is best effort, a log file that can't be written must not crash the UI
 pub fn warn(content: String) {
-    let mut file = OpenOptions::new()
-        .write(true)
+    let file = OpenOptions::new()
         .append(true)
         .create(true)
-        .open("log.txt")
-        .unwrap();
-    let utc: DateTime<Local> = Local::now();
-    writeln!(file, "[{}] {}", utc, content).unwrap();
+        .open("log.txt");
+
+    if let Ok(mut file) = file {
+        let utc: DateTime<Local> = Local::now();
+        let _ = writeln!(file, "[{}] {}", utc, content);
+    }
 }
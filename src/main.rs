@@ -1,16 +1,29 @@
 mod app;
+mod cli;
+mod error;
 mod helper;
 mod ui;
 use crate::{app::init::App, ui::init::Ui};
 use color_eyre::Result;
-use ratatui;
 
 fn main() -> Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if cli::run(&args)? {
+        return Ok(());
+    }
+
+    // Load the configuration before taking the terminal, so errors are printed normally
+    let mut app = App::new()?;
+
+    // A saved conversation can be continued by giving its id
+    if let Some(prefix) = args.first() {
+        app.load_conv(prefix)?;
+    }
+
     // Setup terminal
     let terminal = ratatui::init();
 
     // Run the app
-    let app = App::new();
     let res = Ui::new(app).run(terminal);
 
     // Clean
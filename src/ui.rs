@@ -1,2 +1,5 @@
+pub mod browser;
+pub mod compare;
 pub mod init;
 pub mod inputfield;
+pub mod search;
@@ -0,0 +1,153 @@
+use crate::app::conversation::{self, ConvSummary};
+use crate::app::modules::resume;
+use crate::error::Result;
+use ratatui::{
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, List, ListItem, ListState, Paragraph, Wrap},
+    Frame,
+};
+use uuid::Uuid;
+
+// Side panel listing the saved conversations
+pub struct ConvBrowser {
+    pub visible: bool,
+    pub items: Vec<ConvSummary>,
+    pub state: ListState,
+    pub confirm_delete: bool, // Delete key pressed once, waiting for confirmation
+    pub resume: Option<String>, // Resume file of the selected conversation, when it's shown
+    pub resume_scroll: u16,
+}
+
+impl ConvBrowser {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            items: Vec::new(),
+            state: ListState::default(),
+            confirm_delete: false,
+            resume: None,
+            resume_scroll: 0,
+        }
+    }
+
+    // Read the conversations again, keeping the selection in the list bounds
+    pub fn refresh(&mut self) -> Result<()> {
+        self.items = conversation::list()?;
+        self.confirm_delete = false;
+
+        let selected = match self.items.len() {
+            0 => None,
+            len => Some(self.state.selected().unwrap_or(0).min(len - 1)),
+        };
+        self.state.select(selected);
+        self.reload_resume()
+    }
+
+    // Show the resume file of the selected conversation instead of the messages, or hide it
+    pub fn toggle_resume(&mut self) -> Result<()> {
+        if self.resume.take().is_none() {
+            self.resume = Some(String::new());
+            self.reload_resume()?;
+        }
+        Ok(())
+    }
+
+    // Follow the selection while the resume is shown
+    fn reload_resume(&mut self) -> Result<()> {
+        if self.resume.is_none() {
+            return Ok(());
+        }
+        self.resume_scroll = 0;
+        self.resume = Some(match self.selected() {
+            Some(conv) => resume::load_resume(&conv.id)?.unwrap_or_else(|| {
+                "No resume saved, press s in the conversation to make one.".to_string()
+            }),
+            None => String::new(),
+        });
+        Ok(())
+    }
+
+    pub fn selected(&self) -> Option<&ConvSummary> {
+        self.state.selected().and_then(|i| self.items.get(i))
+    }
+
+    pub fn select_previous(&mut self) -> Result<()> {
+        self.confirm_delete = false;
+        self.state.select_previous();
+        self.reload_resume()
+    }
+
+    pub fn select_next(&mut self) -> Result<()> {
+        self.confirm_delete = false;
+        if self.state.selected().unwrap_or(0) + 1 < self.items.len() {
+            self.state.select_next();
+        }
+        self.reload_resume()
+    }
+
+    pub fn scroll_resume_up(&mut self) {
+        self.resume_scroll = self.resume_scroll.saturating_sub(1);
+    }
+
+    pub fn scroll_resume_down(&mut self) {
+        self.resume_scroll = self.resume_scroll.saturating_add(1);
+    }
+
+    pub fn draw(&mut self, frame: &mut Frame, area: Rect, current: &Uuid) {
+        let items: Vec<ListItem> = self
+            .items
+            .iter()
+            .map(|conv| {
+                // The conversation shown in the chat is highlighted
+                let style = if conv.id == *current {
+                    Style::default().fg(Color::Yellow)
+                } else {
+                    Style::default()
+                };
+                let mut lines = vec![
+                    Line::from(Span::styled(conv.title.clone(), style)),
+                    Line::from(Span::styled(
+                        format!(
+                            "  {} - {} messages",
+                            conv.date.format("%Y-%m-%d %H:%M"),
+                            conv.nb_messages
+                        ),
+                        Style::default().fg(Color::DarkGray),
+                    )),
+                ];
+                if !conv.tags.is_empty() {
+                    lines.push(Line::from(Span::styled(
+                        format!("  #{}", conv.tags.join(" #")),
+                        Style::default().fg(Color::Cyan),
+                    )));
+                }
+                ListItem::new(lines)
+            })
+            .collect();
+
+        let title = if self.confirm_delete {
+            "Press d again to delete"
+        } else {
+            "Conversations"
+        };
+
+        let list = List::new(items)
+            .block(Block::bordered().title(title))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, area, &mut self.state);
+    }
+
+    // Resume of the selected conversation, drawn in place of the messages
+    pub fn draw_resume(&self, frame: &mut Frame, area: Rect) {
+        let Some(resume) = &self.resume else {
+            return;
+        };
+        let resume = Paragraph::new(resume.as_str())
+            .block(Block::bordered().title("Resume"))
+            .wrap(Wrap { trim: false })
+            .scroll((self.resume_scroll, 0));
+        frame.render_widget(resume, area);
+    }
+}
@@ -0,0 +1,77 @@
+use crate::app::compare::Comparison;
+use ratatui::{
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    widgets::{Block, Paragraph, Wrap},
+    Frame,
+};
+
+// Alternative answers side by side, replacing the messages while a comparison is shown
+pub struct ComparePanel {
+    pub selected: usize, // Alternative kept when Enter is pressed
+    pub scroll: u16,     // Lines scrolled, the same in every column
+}
+
+impl ComparePanel {
+    pub fn new() -> Self {
+        Self {
+            selected: 0,
+            scroll: 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.selected = 0;
+        self.scroll = 0;
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn select_next(&mut self, comparison: &Comparison) {
+        if self.selected + 1 < comparison.alternatives.len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn scroll_up(&mut self) {
+        self.scroll = self.scroll.saturating_sub(1);
+    }
+
+    pub fn scroll_down(&mut self) {
+        self.scroll = self.scroll.saturating_add(1);
+    }
+
+    pub fn draw(&self, frame: &mut Frame, area: Rect, comparison: &Comparison) {
+        let constraints = vec![Constraint::Fill(1); comparison.alternatives.len().max(1)];
+        let columns = Layout::horizontal(constraints).split(area);
+
+        for (k, (alternative, column)) in comparison
+            .alternatives
+            .iter()
+            .zip(columns.iter())
+            .enumerate()
+        {
+            let mut title = alternative.label.clone();
+            if !alternative.done {
+                title.push_str(" (answering...)");
+            }
+
+            let border_style = if k == self.selected {
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+
+            let paragraph = Paragraph::new(alternative.message.content.as_str())
+                .style(Style::default().fg(Color::Cyan))
+                .block(Block::bordered().title(title).border_style(border_style))
+                .wrap(Wrap { trim: false })
+                .scroll((self.scroll, 0));
+            frame.render_widget(paragraph, *column);
+        }
+    }
+}
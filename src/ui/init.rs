@@ -1,20 +1,37 @@
 use crate::app::init::App;
 use crate::app::llm::MessageType;
+use crate::ui::browser::ConvBrowser;
+use crate::ui::compare::ComparePanel;
 use crate::ui::inputfield::{BoxData, InputField, InputMode};
+use crate::ui::search::SearchPanel;
 use color_eyre::Result;
 use ratatui::{
-    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
     layout::{Constraint, Layout, Position},
-    style::{Color, Style, Stylize},
+    style::{Color, Modifier, Style, Stylize},
     text::{Line, Text},
     widgets::{Block, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState, Wrap},
     DefaultTerminal, Frame,
 };
+use std::time::Duration;
+use uuid::Uuid;
+
+// Delay between two redraws when no terminal event is received
+const TICK_RATE: Duration = Duration::from_millis(50);
 
 pub struct Ui {
     app: App,
     input_field: InputField,
     message_box_data: BoxData,
+    follow_messages: bool, // Keep the end of the answer visible while it is streamed
+    draft: String,         // Message being written, kept aside while typing a command
+    browser: ConvBrowser,
+    command_target: Option<Uuid>, // Conversation changed by the rename and tag commands, current one if None
+    search: SearchPanel,
+    jump_to: Option<usize>,  // Message to scroll to once drawn
+    selected: Option<usize>, // Message selected in the conversation
+    editing: Option<usize>,  // Message replaced by the one being written
+    compare: ComparePanel,
 }
 
 impl Ui {
@@ -23,16 +40,291 @@ impl Ui {
             app,
             input_field: InputField::new(),
             message_box_data: BoxData::new(),
+            follow_messages: false,
+            draft: String::new(),
+            browser: ConvBrowser::new(),
+            command_target: None,
+            search: SearchPanel::new(),
+            jump_to: None,
+            selected: None,
+            editing: None,
+            compare: ComparePanel::new(),
         }
     }
 
-    // Send the message to the LLM API when "enter" pressed
+    // Send the message to the LLM API when "enter" pressed, the answer is generated in
+    // background
     pub fn submit_message(&mut self) {
-        if self.input_field.input_len() > 0 {
+        if self.input_field.input_len() > 0 && !self.app.is_locked() {
             self.input_field.input_mode = InputMode::Normal;
-            self.app.send_message(self.input_field.input.clone());
+            match self.editing.take() {
+                Some(index) => self.app.edit_message(index, self.input_field.input.clone()),
+                None => self.app.send_message(self.input_field.input.clone()),
+            }
+            self.selected = None;
             self.input_field.input.clear();
             self.input_field.reset_char_index();
+            self.follow_messages = true;
+        }
+    }
+
+    fn start_command(&mut self) {
+        self.draft = self.input_field.take_input();
+        self.input_field.input_mode = InputMode::Command;
+    }
+
+    // Leave the command mode, giving back the message being written
+    fn end_command(&mut self) -> String {
+        let command = self.input_field.take_input();
+        self.input_field.set_input(std::mem::take(&mut self.draft));
+        self.input_field.input_mode = InputMode::Normal;
+        command
+    }
+
+    fn run_command(&mut self) {
+        let command = self.end_command();
+        let (name, args) = command
+            .trim()
+            .split_once(' ')
+            .unwrap_or((command.trim(), ""));
+
+        match name {
+            "open" => {
+                self.app.open_conv(args);
+                self.follow_messages = true;
+            }
+            "new" => self.app.new_conv(),
+            "rename" => {
+                let conv_id = self.command_target.take().unwrap_or(self.app.conv_id);
+                self.app.rename_conv(&conv_id, args);
+            }
+            "tag" => {
+                let conv_id = self.command_target.take().unwrap_or(self.app.conv_id);
+                let tags: Vec<String> =
+                    args.split_whitespace().map(|tag| tag.to_string()).collect();
+                self.app.tag_conv(&conv_id, &tags);
+            }
+            "compare" => {
+                let models: Vec<String> = args
+                    .split_whitespace()
+                    .map(|model| model.to_string())
+                    .collect();
+                self.compare_answer(&models);
+            }
+            "run" => match args.trim() {
+                "" => self.app.run_code(self.selected, None),
+                number => match number.parse() {
+                    Ok(number) => self.app.run_code(self.selected, Some(number)),
+                    Err(_) => self.app.notify(format!("Not a block number: {}", number)),
+                },
+            },
+            "code" => self.app.extract_code(self.selected, args),
+            "attach" => self.app.attach_project(args),
+            "detach" => self.app.detach_project(),
+            "export" => self.app.export_conv(args),
+            "import" => {
+                self.app.import_conv(args);
+                self.follow_messages = true;
+            }
+            "search" => {
+                if let Err(e) = self.search.search(args) {
+                    self.app.notify(e.to_string());
+                }
+            }
+            "" => {}
+            _ => self.app.notify(format!("Unknown command: {}", name)),
+        }
+        self.command_target = None;
+        self.refresh_browser();
+    }
+
+    fn toggle_browser(&mut self) {
+        self.browser.visible = !self.browser.visible;
+        self.browser.resume = None;
+        self.refresh_browser();
+    }
+
+    fn refresh_browser(&mut self) {
+        if self.browser.visible {
+            if let Err(e) = self.browser.refresh() {
+                self.app.notify(e.to_string());
+            }
+        }
+    }
+
+    // Keys of the search results, returns false if the key isn't used by them
+    fn search_key(&mut self, code: KeyCode) -> bool {
+        match code {
+            KeyCode::Up => self.search.select_previous(),
+            KeyCode::Down => self.search.select_next(),
+            KeyCode::Enter => {
+                if let Some(hit) = self.search.selected() {
+                    let (conv_id, index) = (hit.conv_id, hit.index);
+                    if conv_id != self.app.conv_id {
+                        self.app.open_conv(&conv_id.to_string());
+                    }
+                    self.jump_to = self.app.show_position(index);
+                    self.follow_messages = false;
+                }
+            }
+            KeyCode::Esc => self.search.visible = false,
+            _ => return false,
+        }
+        true
+    }
+
+    // Compare the selected answer, or the last one, with the answers of other models
+    fn compare_answer(&mut self, models: &[String]) {
+        self.compare.reset();
+        self.app.compare_answer(self.selected, models);
+    }
+
+    // Keys of the comparison, returns false if the key isn't used by it
+    fn compare_key(&mut self, code: KeyCode) -> bool {
+        let Some(comparison) = self.app.comparison.as_ref() else {
+            return false;
+        };
+        match code {
+            KeyCode::Left => self.compare.select_previous(),
+            KeyCode::Right => self.compare.select_next(comparison),
+            KeyCode::Up => self.compare.scroll_up(),
+            KeyCode::Down => self.compare.scroll_down(),
+            KeyCode::Enter => {
+                self.app.pick_alternative(self.compare.selected);
+                self.selected = None;
+                self.follow_messages = true;
+            }
+            KeyCode::Esc => self.app.close_comparison(),
+            _ => return false,
+        }
+        true
+    }
+
+    // Keys of the conversation browser, returns false if the key isn't used by it
+    fn browser_key(&mut self, code: KeyCode) -> bool {
+        match code {
+            KeyCode::Up => {
+                if let Err(e) = self.browser.select_previous() {
+                    self.app.notify(e.to_string());
+                }
+            }
+            KeyCode::Down => {
+                if let Err(e) = self.browser.select_next() {
+                    self.app.notify(e.to_string());
+                }
+            }
+            KeyCode::PageUp => self.browser.scroll_resume_up(),
+            KeyCode::PageDown => self.browser.scroll_resume_down(),
+            KeyCode::Char('v') => {
+                if let Err(e) = self.browser.toggle_resume() {
+                    self.app.notify(e.to_string());
+                }
+            }
+            KeyCode::Enter => {
+                if let Some(conv) = self.browser.selected() {
+                    let conv_id = conv.id.to_string();
+                    self.app.open_conv(&conv_id);
+                    self.follow_messages = true;
+                }
+            }
+            KeyCode::Char('n') => {
+                self.app.new_conv();
+                self.refresh_browser();
+            }
+            KeyCode::Char('r') => {
+                if let Some(conv) = self.browser.selected() {
+                    self.command_target = Some(conv.id);
+                    let title = conv.title.clone();
+                    self.start_command();
+                    self.input_field.set_input(format!("rename {}", title));
+                }
+            }
+            KeyCode::Char('t') => {
+                if let Some(conv) = self.browser.selected() {
+                    self.command_target = Some(conv.id);
+                    let tags = conv.tags.join(" ");
+                    self.start_command();
+                    self.input_field.set_input(format!("tag {}", tags));
+                }
+            }
+            // Deleting needs to press the key twice
+            KeyCode::Char('d') if self.browser.confirm_delete => {
+                if let Some(conv) = self.browser.selected() {
+                    let conv_id = conv.id;
+                    self.app.delete_conv(&conv_id);
+                }
+                self.refresh_browser();
+            }
+            KeyCode::Char('d') => self.browser.confirm_delete = self.browser.selected().is_some(),
+            KeyCode::Esc | KeyCode::Char('b') => self.toggle_browser(),
+            _ => return false,
+        }
+        true
+    }
+
+    // Keys shared by the message and the command inputs
+    fn edit_input(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Char(to_insert) => self.input_field.enter_char(to_insert),
+            KeyCode::Backspace => self.input_field.delete_char(),
+            KeyCode::Left => self.input_field.move_cursor_left(),
+            KeyCode::Right => self.input_field.move_cursor_right(),
+            KeyCode::Up => self.input_field.move_cursor_up(),
+            KeyCode::Down => self.input_field.move_cursor_down(),
+            _ => {}
+        }
+    }
+
+    // Select the previous (`step` -1) or next (`step` 1) message and scroll to it, going past
+    // the last message unselects it
+    fn select_message(&mut self, step: isize) {
+        let last = self.app.messages.len().checked_sub(1);
+        self.selected = match self.selected {
+            None if step < 0 => last,
+            None => None,
+            Some(index) => index
+                .checked_add_signed(step)
+                .filter(|index| Some(*index) <= last),
+        };
+
+        if self.selected.is_some() {
+            self.jump_to = self.selected;
+            self.follow_messages = false;
+        } else if step > 0 {
+            self.follow_messages = true;
+        } else {
+            self.selected = Some(0);
+        }
+    }
+
+    // Write a new version of the selected message
+    fn edit_selected(&mut self) {
+        let Some(index) = self.selected else {
+            return;
+        };
+        match self.app.messages.get(index) {
+            Some(message) if matches!(message.role, MessageType::USER) && !self.app.is_locked() => {
+                self.editing = Some(index);
+                self.input_field.set_input(message.content.clone());
+                self.input_field.input_mode = InputMode::Editing;
+            }
+            _ => self
+                .app
+                .notify("Select one of your messages to edit it".to_string()),
+        }
+    }
+
+    fn switch_branch(&mut self, step: isize) {
+        if let Some(index) = self.selected {
+            self.app.switch_branch(index, step);
+            self.jump_to = Some(index);
+        }
+    }
+
+    fn resume_conv(&mut self) {
+        if !self.app.is_locked() {
+            self.app.resume_conv();
+            self.follow_messages = true;
         }
     }
 
@@ -42,6 +334,7 @@ impl Ui {
         {
             self.message_box_data.scroll_offset -= 1;
         }
+        self.follow_messages = false;
     }
 
     fn move_messages_down(&mut self) {
@@ -52,180 +345,395 @@ impl Ui {
             > self.message_box_data.max_line
         {
             self.message_box_data.scroll_offset += 1;
+        } else {
+            // Back at the end of the conversation, follow the new messages again
+            self.follow_messages = true;
         }
     }
 
     pub fn run(mut self, mut terminal: DefaultTerminal) -> Result<()> {
         loop {
+            self.app.update();
+            // The messages change when another conversation or branch is shown
+            self.selected = self
+                .selected
+                .filter(|index| *index < self.app.messages.len());
+            if self.follow_messages {
+                self.message_box_data.scroll_offset = self
+                    .message_box_data
+                    .nb_line
+                    .saturating_sub(self.message_box_data.max_line);
+            }
+
             terminal.draw(|frame| self.draw(frame))?;
 
+            // Scroll to the message found by a search, once its position is known
+            if let Some(index) = self.jump_to.take() {
+                if let Some(line) = self.message_box_data.item_lines.get(index) {
+                    self.message_box_data.scroll_offset = *line;
+                }
+            }
+
+            // Don't block on terminal events so the answer keeps being drawn while streamed
+            if !event::poll(TICK_RATE)? {
+                continue;
+            }
+
             if let Event::Key(key) = event::read()? {
+                // The side panel gets the keys first when it's shown
+                if matches!(self.input_field.input_mode, InputMode::Normal) {
+                    if self.compare_key(key.code) {
+                        continue;
+                    }
+                    if self.search.visible && self.search_key(key.code) {
+                        continue;
+                    }
+                    if !self.search.visible && self.browser.visible && self.browser_key(key.code) {
+                        continue;
+                    }
+                }
+
                 match self.input_field.input_mode {
                     InputMode::Normal => match key.code {
+                        KeyCode::Char('e') if self.selected.is_some() => self.edit_selected(),
                         KeyCode::Char('e') => {
                             self.input_field.input_mode = InputMode::Editing;
                         }
+                        KeyCode::Char('k') => self.select_message(-1),
+                        KeyCode::Char('j') => self.select_message(1),
+                        KeyCode::Left if self.selected.is_some() => self.switch_branch(-1),
+                        KeyCode::Right if self.selected.is_some() => self.switch_branch(1),
+                        KeyCode::Esc if self.selected.is_some() => self.selected = None,
                         KeyCode::Char('q') => return Ok(()),
+                        KeyCode::Char(':') => self.start_command(),
+                        KeyCode::Char('b') => self.toggle_browser(),
                         KeyCode::Up => self.move_messages_up(),
                         KeyCode::Down => self.move_messages_down(),
-                        KeyCode::Char('s') => self.app.resume_conv(),
+                        KeyCode::Char('s') => self.resume_conv(),
+                        KeyCode::Char('c') if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            self.compare_answer(&[])
+                        }
+                        KeyCode::Esc => self.app.cancel(),
+                        KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                            self.app.cancel()
+                        }
                         _ => {}
                     },
                     InputMode::Editing if key.kind == KeyEventKind::Press => match key.code {
                         KeyCode::Enter => self.submit_message(),
-                        KeyCode::Char(to_insert) => self.input_field.enter_char(to_insert),
-                        KeyCode::Backspace => self.input_field.delete_char(),
-                        KeyCode::Left => self.input_field.move_cursor_left(),
-                        KeyCode::Right => self.input_field.move_cursor_right(),
-                        KeyCode::Up => self.input_field.move_cursor_up(),
-                        KeyCode::Down => self.input_field.move_cursor_down(),
-                        KeyCode::Esc => self.input_field.input_mode = InputMode::Normal,
-                        _ => {}
+                        KeyCode::Esc => {
+                            // Leaving drops the new version of an edited message
+                            if self.editing.take().is_some() {
+                                self.input_field.take_input();
+                            }
+                            self.input_field.input_mode = InputMode::Normal;
+                        }
+                        code => self.edit_input(code),
                     },
-                    InputMode::Editing => {}
+                    InputMode::Command if key.kind == KeyEventKind::Press => match key.code {
+                        KeyCode::Enter => self.run_command(),
+                        KeyCode::Esc => {
+                            self.end_command();
+                        }
+                        code => self.edit_input(code),
+                    },
+                    InputMode::Editing | InputMode::Command => {}
                 }
             }
         }
     }
 
     fn draw(&mut self, frame: &mut Frame) {
-        let vertical = Layout::vertical([
-            Constraint::Length(1),
-            Constraint::Percentage(90),
-            Constraint::Percentage(10),
-        ]);
-        let [help_area, messages_area, input_area] = vertical.areas(frame.area());
-
-        let help_horizontal =
-            Layout::horizontal([Constraint::Percentage(75), Constraint::Percentage(25)]);
-        let [help_text_area, conv_id_area] = help_horizontal.areas(help_area);
-
-        let (msg, style) = match self.input_field.input_mode {
-            InputMode::Normal => (
-                vec![
-                    "Press ".into(),
-                    "q".bold(),
-                    " to exit, ".into(),
-                    "e".bold(),
-                    " to start editing, ".into(),
-                    "s".bold(),
-                    " to save a resume of the conversation.".into(),
-                ],
-                Style::default(),
-            ),
-            InputMode::Editing => (
-                vec![
-                    "Press ".into(),
-                    "Esc".bold(),
-                    " to stop editing, ".into(),
-                    "Enter".bold(),
-                    " to send the message to Néo AI".into(),
-                ],
-                Style::default(),
-            ),
-        };
-        let help_text = Text::from(Line::from(msg)).patch_style(style);
-        let help_message = Paragraph::new(help_text);
-        frame.render_widget(help_message, help_text_area);
-
-        let conv_id = self.app.conv_id.to_string().clone();
-        let conv_id_text = Paragraph::new(format!("Conv id: {conv_id}"));
-        frame.render_widget(conv_id_text, conv_id_area);
-
-        // Rendering inputfield
-        let input = Paragraph::new(self.input_field.input.as_str())
-            .style(match self.input_field.input_mode {
-                InputMode::Normal => Style::default(),
-                InputMode::Editing => Style::default().fg(Color::Yellow),
-            })
-            .block(Block::bordered().title("Input"))
-            .wrap(Wrap { trim: true })
-            .scroll((self.input_field.input_data.scroll_offset as u16, 0));
-        frame.render_widget(input, input_area);
-
-        self.input_field.update_nb_line(input_area.width);
-        self.input_field
-            .update_max(input_area.width, input_area.height);
-
-        let cursor_y = self.input_field.cursor_y();
-        let cursor_x = self.input_field.cursor_x();
-
-        match self.input_field.input_mode {
-            // Hide the cursor. `Frame` does this by default, so we don't need to do anything here
-            InputMode::Normal => {}
-
-            // Make the cursor visible and ask ratatui to put it at the specified coordinates after
-            // rendering
-            #[allow(clippy::cast_possible_truncation)]
-            InputMode::Editing => frame.set_cursor_position(Position::new(
-                // Draw the cursor at the current position in the input field.
-                // This position is can be controlled via the left and right arrow key
-                input_area.x + cursor_x as u16 + 1,
-                input_area.y + cursor_y as u16,
-            )),
-        }
-        let mut scrollbar_state_input = ScrollbarState::new(self.input_field.input_data.nb_line)
-            .position(self.input_field.input_data.scroll_offset);
-        let scrollbar_input = Scrollbar::new(ScrollbarOrientation::VerticalRight);
-        frame.render_stateful_widget(scrollbar_input, input_area, &mut scrollbar_state_input);
-
-        // Render message list
-        let available_width_message = messages_area.width.saturating_sub(2);
-        let mut messages: Text = Text::default();
-        let mut max_char_per_line = self.message_box_data.max_char_per_line;
-        let mut msg_nb_line: usize = 0;
-
-        for m in &self.app.messages {
-            let msg: String = m.to_string();
-            let size = msg.chars().take(available_width_message as usize).count();
-
-            let text = Text::from(msg);
-            for mut line in text {
-                match m.role {
-                    MessageType::USER => {
-                        line.style = Style::default().fg(Color::Yellow);
-                    }
-                    MessageType::ASSISTANT => {
-                        line.style = Style::default().fg(Color::Cyan);
-                    }
-                    MessageType::SYSTEM => {
-                        line.style = Style::default().fg(Color::Red);
-                    }
-                }
+        draw(frame, self);
+    }
+}
+
+fn draw(frame: &mut Frame, ui: &mut Ui) {
+    let Ui {
+        app,
+        input_field,
+        message_box_data,
+        browser,
+        search,
+        compare,
+        ..
+    } = ui;
+    let selected = ui.selected;
+
+    let vertical = Layout::vertical([
+        Constraint::Length(1),
+        Constraint::Percentage(90),
+        Constraint::Percentage(10),
+    ]);
+    let [help_area, messages_area, input_area] = vertical.areas(frame.area());
+
+    // The search results or the conversation browser take the left of the messages area
+    let messages_area = if search.visible || browser.visible {
+        let [panel_area, messages_area] =
+            Layout::horizontal([Constraint::Percentage(30), Constraint::Percentage(70)])
+                .areas(messages_area);
+        if search.visible {
+            search.draw(frame, panel_area);
+        } else {
+            browser.draw(frame, panel_area, &app.conv_id);
+        }
+        messages_area
+    } else {
+        messages_area
+    };
+
+    let help_horizontal =
+        Layout::horizontal([Constraint::Percentage(75), Constraint::Percentage(25)]);
+    let [help_text_area, conv_id_area] = help_horizontal.areas(help_area);
+
+    let (msg, style) = match input_field.input_mode {
+        InputMode::Normal if app.is_busy() && app.comparison.is_none() => (
+            vec![
+                "Press ".into(),
+                "q".bold(),
+                " to exit, ".into(),
+                "Esc".bold(),
+                " or ".into(),
+                "Ctrl-C".bold(),
+                " to cancel the answer.".into(),
+            ],
+            Style::default(),
+        ),
+        InputMode::Normal if app.comparison.is_some() => (
+            vec![
+                "Press ".into(),
+                "Left".bold(),
+                "/".into(),
+                "Right".bold(),
+                " to choose an answer, ".into(),
+                "Up".bold(),
+                "/".into(),
+                "Down".bold(),
+                " to scroll, ".into(),
+                "Enter".bold(),
+                " to keep it, ".into(),
+                "Esc".bold(),
+                " to keep the current answer.".into(),
+            ],
+            Style::default(),
+        ),
+        InputMode::Normal if selected.is_some() => (
+            vec![
+                "Press ".into(),
+                "e".bold(),
+                " to edit the message and answer again, ".into(),
+                "Left".bold(),
+                "/".into(),
+                "Right".bold(),
+                " to show its other versions, ".into(),
+                "c".bold(),
+                " to compare answers, ".into(),
+                "j".bold(),
+                "/".into(),
+                "k".bold(),
+                " to select another one, ".into(),
+                "Esc".bold(),
+                " to unselect.".into(),
+            ],
+            Style::default(),
+        ),
+        InputMode::Normal => (
+            vec![
+                "Press ".into(),
+                "q".bold(),
+                " to exit, ".into(),
+                "k".bold(),
+                " to select a message, ".into(),
+                "e".bold(),
+                " to start editing, ".into(),
+                "s".bold(),
+                " to save a resume of the conversation, ".into(),
+                "b".bold(),
+                " to browse conversations, ".into(),
+                ":".bold(),
+                " to type a command.".into(),
+            ],
+            Style::default(),
+        ),
+        InputMode::Editing => (
+            vec![
+                "Press ".into(),
+                "Esc".bold(),
+                " to stop editing, ".into(),
+                "Enter".bold(),
+                " to send the message to Néo AI".into(),
+            ],
+            Style::default(),
+        ),
+        InputMode::Command => (
+            vec![
+                "Press ".into(),
+                "Esc".bold(),
+                " to cancel, ".into(),
+                "Enter".bold(),
+                " to run: ".into(),
+                "open <id>".bold(),
+                ", ".into(),
+                "new".bold(),
+                ", ".into(),
+                "rename <title>".bold(),
+                ", ".into(),
+                "tag <tags>".bold(),
+                ", ".into(),
+                "export md|html|json".bold(),
+                ", ".into(),
+                "import <file>".bold(),
+                ", ".into(),
+                "compare [models]".bold(),
+                ", ".into(),
+                "run [block]".bold(),
+                ", ".into(),
+                "code [block] [file]".bold(),
+                ", ".into(),
+                "attach <dir>".bold(),
+                ", ".into(),
+                "search <words>".bold(),
+            ],
+            Style::default(),
+        ),
+    };
+    let help_text = Text::from(Line::from(msg)).patch_style(style);
+    let help_message = Paragraph::new(help_text);
+    frame.render_widget(help_message, help_text_area);
+
+    // The start of the id is enough to open the conversation again
+    let conv_id = app.conv_id.to_string();
+    let conv_id_text = match &app.title {
+        Some(title) => Paragraph::new(format!("{title} ({})", &conv_id[..8])),
+        None => Paragraph::new(format!("Conv id: {conv_id}")),
+    };
+    frame.render_widget(conv_id_text, conv_id_area);
 
-                messages.push_line(line.clone());
-                let line_count =
-                    (line.to_string().chars().count() as f64 / size as f64).ceil() as usize;
+    // Rendering inputfield
+    let input = Paragraph::new(input_field.input.as_str())
+        .style(match input_field.input_mode {
+            InputMode::Normal => Style::default(),
+            InputMode::Editing => Style::default().fg(Color::Yellow),
+            InputMode::Command => Style::default().fg(Color::Green),
+        })
+        .block(Block::bordered().title(match input_field.input_mode {
+            InputMode::Command => "Command",
+            _ => "Input",
+        }))
+        .wrap(Wrap { trim: true })
+        .scroll((input_field.input_data.scroll_offset as u16, 0));
+    frame.render_widget(input, input_area);
 
-                if line_count > 0 {
-                    msg_nb_line += line_count;
-                } else {
-                    msg_nb_line += 1;
+    input_field.update_nb_line(input_area.width);
+    input_field.update_max(input_area.width, input_area.height);
+
+    let cursor_y = input_field.cursor_y();
+    let cursor_x = input_field.cursor_x();
+
+    match input_field.input_mode {
+        // Hide the cursor. `Frame` does this by default, so we don't need to do anything here
+        InputMode::Normal => {}
+
+        // Make the cursor visible and ask ratatui to put it at the specified coordinates after
+        // rendering
+        #[allow(clippy::cast_possible_truncation)]
+        InputMode::Editing | InputMode::Command => frame.set_cursor_position(Position::new(
+            // Draw the cursor at the current position in the input field.
+            // This position is can be controlled via the left and right arrow key
+            input_area.x + cursor_x as u16 + 1,
+            input_area.y + cursor_y as u16,
+        )),
+    }
+    let mut scrollbar_state_input = ScrollbarState::new(input_field.input_data.nb_line)
+        .position(input_field.input_data.scroll_offset);
+    let scrollbar_input = Scrollbar::new(ScrollbarOrientation::VerticalRight);
+    frame.render_stateful_widget(scrollbar_input, input_area, &mut scrollbar_state_input);
+
+    // Render message list
+    let available_width_message = messages_area.width.saturating_sub(2);
+    let mut messages: Text = Text::default();
+    let mut max_char_per_line = message_box_data.max_char_per_line;
+    let mut msg_nb_line: usize = 0;
+
+    // The answer being generated is drawn after the history
+    message_box_data.item_lines.clear();
+    for (i, m) in app.messages.iter().chain(app.pending.iter()).enumerate() {
+        message_box_data.item_lines.push(msg_nb_line);
+        // Messages edited or answered again show which version is shown
+        let msg: String = match app.versions(i) {
+            Some((number, total)) if total > 1 => format!("[{}/{}] {}", number, total, m),
+            _ => m.to_string(),
+        };
+        let size = msg.chars().take(available_width_message as usize).count();
+
+        let text = Text::from(msg);
+        for mut line in text {
+            match m.role {
+                MessageType::USER => {
+                    line.style = Style::default().fg(Color::Yellow);
+                }
+                MessageType::ASSISTANT => {
+                    line.style = Style::default().fg(Color::Cyan);
+                }
+                MessageType::SYSTEM => {
+                    line.style = Style::default().fg(Color::Red);
                 }
             }
+            if selected == Some(i) {
+                line.style = line.style.add_modifier(Modifier::REVERSED);
+            }
+
+            messages.push_line(line.clone());
+            let line_count =
+                (line.to_string().chars().count() as f64 / size as f64).ceil() as usize;
 
-            if size > max_char_per_line {
-                max_char_per_line = size;
+            if line_count > 0 {
+                msg_nb_line += line_count;
+            } else {
+                msg_nb_line += 1;
             }
         }
 
-        let messages = Paragraph::new(Text::from(messages))
-            .block(Block::bordered().title("Chat with Néo AI"))
-            .wrap(Wrap { trim: false })
-            .scroll((self.message_box_data.scroll_offset as u16, 0));
-        frame.render_widget(messages, messages_area);
+        if size > max_char_per_line {
+            max_char_per_line = size;
+        }
+    }
 
-        self.message_box_data.max_char_per_line = max_char_per_line;
-        self.message_box_data.nb_line = msg_nb_line;
-        self.message_box_data.max_line = messages_area.height.saturating_sub(2) as usize;
+    let mut title = String::from("Chat with Néo AI");
+    if app.is_busy() {
+        title.push_str(" (answering...)");
+    }
+    // Context window used by the last request, to know when the history starts to be reduced
+    if let Some(usage) = app.context {
+        let percent = usage.used * 100 / usage.budget.max(1);
+        title.push_str(&format!(
+            " - context {}/{} tokens ({}%)",
+            usage.used, usage.budget, percent
+        ));
+    }
 
-        let mut scrollbar_state_message = ScrollbarState::new(self.message_box_data.nb_line)
-            .position(self.message_box_data.scroll_offset);
-        let scrollbar_message = Scrollbar::new(ScrollbarOrientation::VerticalRight);
-        frame.render_stateful_widget(
-            scrollbar_message,
-            messages_area,
-            &mut scrollbar_state_message,
-        );
+    let messages = Paragraph::new(messages)
+        .block(Block::bordered().title(title))
+        .wrap(Wrap { trim: false })
+        .scroll((message_box_data.scroll_offset as u16, 0));
+    match &app.comparison {
+        Some(comparison) => compare.draw(frame, messages_area, comparison),
+        None if browser.visible && browser.resume.is_some() => {
+            browser.draw_resume(frame, messages_area)
+        }
+        None => frame.render_widget(messages, messages_area),
     }
+
+    message_box_data.max_char_per_line = max_char_per_line;
+    message_box_data.nb_line = msg_nb_line;
+    message_box_data.max_line = messages_area.height.saturating_sub(2) as usize;
+
+    let mut scrollbar_state_message =
+        ScrollbarState::new(message_box_data.nb_line).position(message_box_data.scroll_offset);
+    let scrollbar_message = Scrollbar::new(ScrollbarOrientation::VerticalRight);
+    frame.render_stateful_widget(
+        scrollbar_message,
+        messages_area,
+        &mut scrollbar_state_message,
+    );
 }
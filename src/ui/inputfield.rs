@@ -3,6 +3,7 @@ pub struct BoxData {
     pub max_line: usize,
     pub nb_line: usize,
     pub scroll_offset: usize,
+    pub item_lines: Vec<usize>, // First line of each item (message) in the box
 }
 
 pub struct InputField {
@@ -15,6 +16,7 @@ pub struct InputField {
 pub enum InputMode {
     Normal,
     Editing,
+    Command, // Typing a command, e.g. `open <id>`
 }
 
 impl BoxData {
@@ -24,6 +26,7 @@ impl BoxData {
             max_line: 1,
             nb_line: 0,
             scroll_offset: 0,
+            item_lines: Vec::new(),
         }
     }
 }
@@ -86,6 +89,19 @@ impl InputField {
         new_cursor_pos.clamp(0, self.input.chars().count())
     }
 
+    // Empty the inputfield and return its content
+    pub fn take_input(&mut self) -> String {
+        let input = std::mem::take(&mut self.input);
+        self.reset_char_index();
+        input
+    }
+
+    // Replace the inputfield content, the cursor is put at the end
+    pub fn set_input(&mut self, input: String) {
+        self.input = input;
+        self.character_index = self.input.chars().count();
+    }
+
     pub fn reset_char_index(&mut self) {
         self.character_index = 0;
         self.input_data.scroll_offset = 0;
@@ -154,18 +170,18 @@ impl InputField {
                 self.input_data.scroll_offset = y;
             }
 
-            return y.max(1);
+            y.max(1)
         } else {
-            return 1;
+            1
         }
     }
 
     // Calculate cursor_x position
     pub fn cursor_x(&mut self) -> usize {
         if self.input_data.nb_line > 1 {
-            return self.character_index % self.input_data.max_char_per_line;
+            self.character_index % self.input_data.max_char_per_line
         } else {
-            return self.character_index;
+            self.character_index
         }
     }
 }
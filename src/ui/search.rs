@@ -0,0 +1,82 @@
+use crate::app::llm::MessageType;
+use crate::app::search::{self, SearchHit};
+use crate::error::Result;
+use ratatui::{
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, List, ListItem, ListState},
+    Frame,
+};
+
+// Side panel listing the messages matching a search
+pub struct SearchPanel {
+    pub visible: bool,
+    pub query: String,
+    pub hits: Vec<SearchHit>,
+    pub state: ListState,
+}
+
+impl SearchPanel {
+    pub fn new() -> Self {
+        Self {
+            visible: false,
+            query: String::new(),
+            hits: Vec::new(),
+            state: ListState::default(),
+        }
+    }
+
+    pub fn search(&mut self, query: &str) -> Result<()> {
+        self.query = query.trim().to_string();
+        self.hits = search::search(&self.query)?;
+        self.state
+            .select(if self.hits.is_empty() { None } else { Some(0) });
+        self.visible = true;
+        Ok(())
+    }
+
+    pub fn selected(&self) -> Option<&SearchHit> {
+        self.state.selected().and_then(|i| self.hits.get(i))
+    }
+
+    pub fn select_previous(&mut self) {
+        self.state.select_previous();
+    }
+
+    pub fn select_next(&mut self) {
+        if self.state.selected().unwrap_or(0) + 1 < self.hits.len() {
+            self.state.select_next();
+        }
+    }
+
+    pub fn draw(&mut self, frame: &mut Frame, area: Rect) {
+        let items: Vec<ListItem> = self
+            .hits
+            .iter()
+            .map(|hit| {
+                let (role, color) = match hit.role {
+                    MessageType::USER => ("You", Color::Yellow),
+                    MessageType::ASSISTANT => ("Néo AI", Color::Cyan),
+                    MessageType::SYSTEM => ("System", Color::Red),
+                };
+                ListItem::new(vec![
+                    Line::from(Span::styled(
+                        format!("{} ({})", hit.conv_title, &hit.conv_id.to_string()[..8]),
+                        Style::default().fg(Color::DarkGray),
+                    )),
+                    Line::from(vec![
+                        Span::styled(format!("{}: ", role), Style::default().fg(color)),
+                        Span::raw(hit.snippet.clone()),
+                    ]),
+                ])
+            })
+            .collect();
+
+        let title = format!("Search \"{}\" - {} results", self.query, self.hits.len());
+        let list = List::new(items)
+            .block(Block::bordered().title(title))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, area, &mut self.state);
+    }
+}